@@ -0,0 +1,130 @@
+use std::io;
+use std::iter::empty;
+
+use console::{style, Term};
+use boolinator::Boolinator;
+use num_traits::cast::ToPrimitive;
+
+use crate::intcode_full::{State, YieldReason, InputWord, Word};
+
+const NEWLINE: InputWord = 10;
+const MAX_ASCII: u8 = 127;
+
+pub struct Ascii {
+    state: State,
+    line: String
+}
+
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct Screen {
+    pub lines: Vec<String>,
+    pub non_ascii: Vec<Word>
+}
+
+fn ascii_char(w: &Word) -> Option<char> {
+    let c = w.to_u8()?;
+    (c <= MAX_ASCII).as_some(c as char)
+}
+
+impl Ascii {
+    pub fn new(state: State) -> Self {
+        Ascii { state, line: String::new() }
+    }
+
+    pub fn new_from_str(s: &str) -> Self {
+        Self::new(State::new_from_str(s, empty()))
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    pub fn into_inner(self) -> State {
+        self.state
+    }
+
+    pub fn pending_line(&self) -> &str {
+        &self.line
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        for c in line.chars() {
+            self.state.supply_input(c as InputWord);
+        }
+
+        self.state.supply_input(NEWLINE);
+    }
+
+    pub fn run_to_yield(&mut self) -> (Screen, YieldReason) {
+        let (outputs, reason) = self.state.run_to_yield();
+        let mut screen = Screen::default();
+
+        for output in outputs {
+            match ascii_char(&output) {
+                Some('\n') => screen.lines.push(std::mem::take(&mut self.line)),
+                Some(c) => self.line.push(c),
+                None => screen.non_ascii.push(output)
+            }
+        }
+
+        if reason != YieldReason::WaitInput && !self.line.is_empty() {
+            screen.lines.push(std::mem::take(&mut self.line));
+        }
+
+        (screen, reason)
+    }
+
+    pub fn interact(&mut self, term: &Term) -> io::Result<YieldReason> {
+        loop {
+            let (screen, reason) = self.run_to_yield();
+
+            for line in screen.lines.iter() {
+                term.write_line(line)?;
+            }
+
+            for word in screen.non_ascii.iter() {
+                term.write_line(&style(word).bold().to_string())?;
+            }
+
+            if reason != YieldReason::WaitInput {
+                return Ok(reason);
+            }
+
+            term.write_str(&self.line)?;
+            self.line.clear();
+
+            let input = term.read_line()?;
+            self.send_line(&input);
+        }
+    }
+}
+
+#[test]
+fn lines_and_non_ascii() {
+    let code = "104,72,104,105,104,10,104,1000,104,111,104,107,99";
+    let mut ascii = Ascii::new_from_str(code);
+
+    let (screen, reason) = ascii.run_to_yield();
+    assert_eq!(reason, YieldReason::Halt);
+    assert_eq!(screen.lines, ["Hi", "ok"]);
+    assert_eq!(screen.non_ascii, [Word::from(1000)]);
+}
+
+#[test]
+fn echo_line() {
+    let echo = "3,100,4,100,1008,100,10,101,1006,101,0,99";
+    let mut ascii = Ascii::new_from_str(echo);
+
+    let (screen, reason) = ascii.run_to_yield();
+    assert_eq!(reason, YieldReason::WaitInput);
+    assert!(screen.lines.is_empty());
+
+    ascii.send_line("hello");
+    let (screen, reason) = ascii.run_to_yield();
+    assert_eq!(reason, YieldReason::Halt);
+    assert_eq!(screen.lines, ["hello"]);
+}
@@ -0,0 +1,138 @@
+use std::thread;
+use std::iter::empty;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::intcode_full::{State, YieldReason, InputWord, Word};
+
+#[derive(Clone, Default, Debug)]
+pub struct Variant {
+    pub patches: Vec<(InputWord, InputWord)>,
+    pub inputs: Vec<InputWord>
+}
+
+pub struct Run {
+    pub outputs: Vec<Word>,
+    pub reason: YieldReason,
+    pub state: State
+}
+
+pub fn workers() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+pub fn evaluate<T, R, F, P>(items: &[T], workers: usize, f: F, stop: P) -> Vec<Option<R>>
+    where T: Sync, R: Send, F: Fn(&T) -> R + Sync, P: Fn(&R) -> bool + Sync
+{
+    let next = AtomicUsize::new(0);
+    let limit = AtomicUsize::new(items.len());
+
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.max(1))
+            .map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= limit.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let result = f(&items[i]);
+                    if stop(&result) {
+                        limit.fetch_min(i + 1, Ordering::SeqCst);
+                    }
+
+                    done.push((i, result));
+                }
+
+                done
+            }))
+            .collect();
+
+        for handle in handles {
+            for (i, result) in handle.join().expect("Worker panicked!") {
+                results[i] = Some(result);
+            }
+        }
+    });
+
+    results
+}
+
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+    where T: Sync, R: Send, F: Fn(&T) -> R + Sync
+{
+    evaluate(items, workers(), f, |_| false)
+        .into_iter()
+        .map(|r| r.expect("Every item is evaluated."))
+        .collect()
+}
+
+pub fn find_first<T, R, F, P>(items: &[T], f: F, pred: P) -> Option<(usize, R)>
+    where T: Sync, R: Send, F: Fn(&T) -> R + Sync, P: Fn(&R) -> bool + Sync
+{
+    evaluate(items, workers(), f, &pred)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, r)| Some((i, r?)))
+        .find(|(_, r)| pred(r))
+}
+
+fn run_variant(base: &State, variant: &Variant) -> Run {
+    let mut state = base.clone();
+
+    for &(pos, value) in variant.patches.iter() {
+        state.write_memory(pos, value);
+    }
+
+    for &input in variant.inputs.iter() {
+        state.supply_input(input);
+    }
+
+    let (outputs, reason) = state.run_to_yield();
+    Run { outputs, reason, state }
+}
+
+pub fn run_variants(program: &str, variants: &[Variant]) -> Vec<Run> {
+    let base = State::new_from_str(program, empty());
+    map(variants, |v| run_variant(&base, v))
+}
+
+pub fn find_variant<P>(program: &str, variants: &[Variant], pred: P) -> Option<(usize, Run)>
+    where P: Fn(&Run) -> bool + Sync
+{
+    let base = State::new_from_str(program, empty());
+    find_first(variants, |v| run_variant(&base, v), pred)
+}
+
+#[test]
+fn results_in_input_order() {
+    let items: Vec<usize> = (0..100).collect();
+    assert_eq!(evaluate(&items, 4, |&x| x * x, |_| false), items.iter().map(|&x| Some(x * x)).collect::<Vec<_>>());
+}
+
+#[test]
+fn stops_at_first_match() {
+    let items: Vec<usize> = (0..10_000).collect();
+
+    let results = evaluate(&items, 1, |&x| x, |&x| x >= 10);
+    assert_eq!(results.iter().filter(|r| r.is_some()).count(), 11);
+
+    let results = evaluate(&items, 4, |&x| x, |&x| x >= 10);
+    assert!(results[..=10].iter().all(Option::is_some));
+    assert_eq!(find_first(&items, |&x| x * 3, |&x| x % 7 == 6), Some((2, 6)));
+}
+
+#[test]
+fn noun_verb_variants() {
+    let program = "1,0,0,0,99,5,6";
+    let variants: Vec<_> = (0..7)
+        .flat_map(|noun| (0..7).map(move |verb| Variant { patches: vec![(1, noun), (2, verb)], inputs: Vec::new() }))
+        .collect();
+
+    let (i, run) = find_variant(program, &variants, |run| run.state.read(&0usize.into()) == Word::from(11)).unwrap();
+    assert_eq!(variants[i].patches, [(1, 5), (2, 6)]);
+    assert_eq!(run.reason, YieldReason::Halt);
+}
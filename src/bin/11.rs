@@ -3,11 +3,18 @@ use std::convert::{From, Into};
 use std::collections::HashMap;
 
 use boolinator::Boolinator;
+use num_traits::cast::ToPrimitive;
 use itertools::{Itertools, join};
 
-use aoc2019::intcode_full::{State, YieldReason, InputWord, word_narrow};
+use aoc2019::intcode_full::{State, YieldReason, Until, Event, Word};
+use aoc2019::transcript::Machine;
+#[cfg(test)]
+use aoc2019::transcript::{Recorder, Transcript};
+use aoc2019::coredump::{crash, HISTORY};
 
 const INPUT: &'static str = include_str!("inputs/11.txt");
+#[cfg(test)]
+const TRANSCRIPT: &str = include_str!("transcripts/11.txt");
 
 #[derive(Copy, Clone)]
 struct Dir {
@@ -21,21 +28,21 @@ enum Color {
     White
 }
 
-impl From<InputWord> for Color {
-    fn from(c: InputWord) -> Color {
-        match c {
-            0 => Color::Black,
-            1 => Color::White,
+impl From<&Word> for Color {
+    fn from(c: &Word) -> Color {
+        match c.to_u8() {
+            Some(0) => Color::Black,
+            Some(1) => Color::White,
             _ => panic!("Unknown color!")
         }
     }
 }
 
-impl Into<InputWord> for Color {
-    fn into(self) -> InputWord {
+impl Into<Word> for Color {
+    fn into(self) -> Word {
         match self {
-            Color::Black => 0,
-            Color::White => 1
+            Color::Black => 0.into(),
+            Color::White => 1.into()
         }
     }
 }
@@ -86,8 +93,7 @@ impl Bitmap {
     }
 }
 
-fn produce_bitmap(s: &str, start_color: Color) -> Bitmap {
-    let mut state = State::new_from_str(s, empty());
+fn produce_bitmap<M: Machine>(state: &mut M, start_color: Color) -> Bitmap {
     let mut bitmap = Bitmap::new();
 
     let mut pos = Pixel { x: 0, y: 0 };
@@ -96,20 +102,18 @@ fn produce_bitmap(s: &str, start_color: Color) -> Bitmap {
     bitmap.paint(pos, start_color);
 
     loop {
-        state.supply_input(bitmap.at(pos).into());
+        state.supply_word(bitmap.at(pos).into());
 
-        let (outputs, yield_reason) = state.run_to_yield();
+        let (outputs, event) = state.run_until(&[Until::Outputs(2)]);
 
-        match yield_reason {
-            YieldReason::Halt => break,
-            YieldReason::WaitInput => {
-                assert!(outputs.len() == 2);
-
-                let color: Color = word_narrow(outputs[0].clone()).expect("Cannot narrow!").into();
+        match event {
+            Event::Yield(YieldReason::Halt) => break,
+            Event::Outputs => {
+                let color = Color::from(&outputs[0]);
 
                 bitmap.paint(pos, color);
 
-                match word_narrow(outputs[1].clone()) {
+                match outputs[1].to_u8() {
                     Some(0) => dir = dir.rotate_left(),
                     Some(1) => dir = dir.rotate_right(),
                     _ => panic!("Incorrect direction!")
@@ -117,7 +121,8 @@ fn produce_bitmap(s: &str, start_color: Color) -> Bitmap {
 
                 pos = pos.step(dir);
             },
-            _ => panic!("Incorrect program flow!")
+            Event::Yield(reason) => crash(state.state(), reason, &outputs, "11.core"),
+            _ => unreachable!()
         }
 
     }
@@ -125,13 +130,20 @@ fn produce_bitmap(s: &str, start_color: Color) -> Bitmap {
     bitmap
 }
 
+fn robot() -> State {
+    let mut state = State::new_from_str(INPUT, empty());
+    state.keep_history(HISTORY);
+    state.track_calls();
+    state
+}
+
 fn part_one() {
-    let bitmap = produce_bitmap(INPUT, Color::Black);
+    let bitmap = produce_bitmap(&mut robot(), Color::Black);
     println!("{}", bitmap.painted());
 }
 
 fn part_two() {
-    let bitmap = produce_bitmap(INPUT, Color::White);
+    let bitmap = produce_bitmap(&mut robot(), Color::White);
 
     let (min_x, max_x) = bitmap.white_pixels().map(|Pixel { x, .. }| x).minmax().into_option().unwrap();
     let (min_y, max_y) = bitmap.white_pixels().map(|Pixel { y, .. }| y).minmax().into_option().unwrap();
@@ -147,6 +159,20 @@ fn part_two() {
     }
 }
 
+#[test]
+#[ignore]
+fn record_transcript() {
+    let mut recorder = Recorder::new_from_str(INPUT, empty());
+    produce_bitmap(&mut recorder, Color::White);
+    std::fs::write("src/bin/transcripts/11.txt", recorder.transcript().to_string()).expect("Writable!");
+}
+
+#[test]
+fn transcript_regression() {
+    let transcript = Transcript::parse(TRANSCRIPT).expect("Transcript is correct!");
+    assert_eq!(transcript.replay(INPUT), Ok(()));
+}
+
 fn main() {
     part_one();
     part_two();
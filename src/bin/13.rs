@@ -9,9 +9,16 @@ use std::collections::HashMap;
 
 use boolinator::Boolinator;
 use itertools::Itertools;
-use aoc2019::intcode_full::{State, YieldReason, InputWord, word_narrow};
+use num_traits::cast::ToPrimitive;
+use aoc2019::intcode_full::{State, YieldReason, Until, Event, InputWord};
+use aoc2019::transcript::Machine;
+use aoc2019::coredump::{crash, HISTORY};
+#[cfg(test)]
+use aoc2019::transcript::{Recorder, Transcript};
 
 const INPUT: &'static str = include_str!("inputs/13.txt");
+#[cfg(test)]
+const TRANSCRIPT: &str = include_str!("transcripts/13.txt");
 const FRAME_RATE: Duration = Duration::from_millis(100);
 const SKIP_FRAME: usize = 100;
 
@@ -43,14 +50,22 @@ fn iw2t(iw: InputWord) -> Option<Tile> {
     }
 }
 
-fn tiles(state: &mut State) -> Option<(YieldReason, Option<InputWord>, Tiles)> {
-    let (outputs, yield_reason) = state.run_to_yield();
-
+fn tiles<M: Machine>(state: &mut M) -> Option<(YieldReason, Option<InputWord>, Tiles)> {
     let mut score: Option<InputWord> = None;
     let mut tiles: Tiles = Default::default();
 
-    for (xb, yb, tb) in outputs.into_iter().tuples() {
-        let (x, y, t) = (word_narrow(xb)?, word_narrow(yb)?, word_narrow(tb)?);
+    loop {
+        let (outputs, event) = state.run_until(&[Until::Outputs(3)]);
+
+        match event {
+            Event::Outputs => (),
+            Event::Yield(yield_reason) if outputs.is_empty() => return Some((yield_reason, score, tiles)),
+            Event::Yield(yield_reason) if yield_reason.is_fault() => crash(state.state(), yield_reason, &outputs, "13.core"),
+            _ => return None
+        }
+
+        let (x, y, t) = outputs.iter().map(ToPrimitive::to_isize).collect_tuple()?;
+        let (x, y, t) = (x?, y?, t?);
 
         if x == -1 && y == 0 {
             score.replace(t);
@@ -60,8 +75,6 @@ fn tiles(state: &mut State) -> Option<(YieldReason, Option<InputWord>, Tiles)> {
             tiles.insert(Pos { x, y }, tile);
         }
     }
-
-    Some((yield_reason, score, tiles))
 }
 
 fn merge_tiles(accum: &mut Tiles, new: &Tiles) {
@@ -107,37 +120,65 @@ fn part_one() {
     println!("{}", block_tiles);
 }
 
-fn part_two() {
-    let mut state = State::new_from_str(INPUT, empty());
-    state.write_memory(0, 2);
-
+fn play<M: Machine, F: FnMut(&Tiles, Option<InputWord>)>(state: &mut M, mut draw: F) -> Option<InputWord> {
     let mut accum_tiles = Default::default();
     let mut accum_score = None;
 
     for frame in 0usize.. {
-        let (yield_reason, score, tiles) = tiles(&mut state).expect("Examples are correct!");
+        let (yield_reason, score, tiles) = tiles(state).expect("Examples are correct!");
         merge_tiles(&mut accum_tiles, &tiles);
         score.map(|score| accum_score.replace(score));
 
         let (ball, paddle) = ball_and_paddle(&accum_tiles).expect("There is ball and paddle!");
 
-        let redraw = match yield_reason {
-            YieldReason::Halt => true,
-            YieldReason::WaitInput => {
-                state.supply_input((ball.x - paddle.x).signum());
-                frame % SKIP_FRAME == 0
+        match yield_reason {
+            YieldReason::Halt => {
+                draw(&accum_tiles, accum_score);
+                break;
             },
-            _ => panic!("Incorrect IntCode program!")
-        };
-
-        if redraw {
-            console::Term::stdout().clear_screen();
+            YieldReason::WaitInput => {
+                state.supply_word((ball.x - paddle.x).signum().into());
 
-            println!("Score: {:?}", accum_score.unwrap_or(0));
-            draw_screen(&accum_tiles);
-            sleep(FRAME_RATE);
+                if frame % SKIP_FRAME == 0 {
+                    draw(&accum_tiles, accum_score);
+                }
+            },
+            _ => crash(state.state(), yield_reason, &[], "13.core")
         }
     }
+
+    accum_score
+}
+
+fn redraw(tiles: &Tiles, score: Option<InputWord>) {
+    console::Term::stdout().clear_screen();
+
+    println!("Score: {:?}", score.unwrap_or(0));
+    draw_screen(tiles);
+    sleep(FRAME_RATE);
+}
+
+fn part_two() {
+    let mut state = State::new_from_str(INPUT, empty());
+    state.write_memory(0, 2);
+    state.keep_history(HISTORY);
+    state.track_calls();
+    play(&mut state, redraw);
+}
+
+#[test]
+#[ignore]
+fn record_transcript() {
+    let mut state = Recorder::new_from_str(INPUT, empty());
+    state.write_memory(0, 2);
+    play(&mut state, |_, _| ());
+    std::fs::write("src/bin/transcripts/13.txt", state.transcript().to_string()).expect("Writable!");
+}
+
+#[test]
+fn transcript_regression() {
+    let transcript = Transcript::parse(TRANSCRIPT).expect("Transcript is correct!");
+    assert_eq!(transcript.replay(INPUT), Ok(()));
 }
 
 fn main() {
@@ -1,8 +1,17 @@
 #[allow(unused_imports)]
 
 use std::convert::TryInto;
-use itertools::{join, iproduct};
+use itertools::join;
+
+use aoc2019::symbolic::{Symbolic, SymbolicError};
+use aoc2019::intcode_full::{Word, word_narrow};
+
+#[cfg(test)]
+use itertools::iproduct;
+#[cfg(test)]
 use boolinator::Boolinator;
+#[cfg(test)]
+use aoc2019::difftest::{self, Case, Outcome, Reason, DAY_2};
 
 const INPUT: &'static str = include_str!("inputs/2.txt");
 
@@ -80,12 +89,32 @@ fn example_final_states() {
     assert_eq!(final_state("1,1,1,4,99,5,6,0,99"), "30,1,1,4,2,5,6,0,99");
 }
 
+#[cfg(test)]
+fn run_case(case: &Case) -> Outcome {
+    let mut state = State::new_from_str(&case.program_str());
+    state.run_to_halt();
+
+    Outcome {
+        outputs: Vec::new(),
+        memory: state.memory.iter().map(|&w| w as isize).collect(),
+        reason: Reason::Halt
+    }
+}
+
+#[test]
+fn differential() {
+    difftest::check(&DAY_2, 0..300, &[("bin/2", &run_case),
+                                       ("intcode", &difftest::run_intcode),
+                                       ("intcode_full", &difftest::run_intcode_full)]);
+}
+
 #[derive(Copy, Clone)]
 struct Params {
     noun: usize,
     verb: usize
 }
 
+#[cfg(test)]
 impl Params {
     fn enumerate() -> impl Iterator<Item=Params> {
         iproduct!(0..=99, 0..=99).map(|(noun, verb)| Params { noun, verb })
@@ -106,12 +135,44 @@ fn part_one() {
     println!("{}", get_output(INPUT, Params { noun: 12, verb: 2 }));
 }
 
-fn find_noun_verb() -> Option<Params> {
+#[cfg(test)]
+fn brute_force_noun_verb() -> Option<Params> {
     Params::enumerate().filter_map(|p| (get_output(INPUT, p) == 19690720).as_some(p)).nth(0)
 }
 
+fn find_noun_verb() -> Result<Option<Params>, SymbolicError> {
+    let mut symbolic = Symbolic::new_from_str(INPUT);
+    let noun = symbolic.unknown_memory(1, 0..=99);
+    let verb = symbolic.unknown_memory(2, 0..=99);
+
+    let solution = symbolic.solve(|path| Some(vec![path.memory(0).linear()?.equals(19690720)]))?;
+    let narrow = |solution: &[Word], v: usize| word_narrow(solution[v].clone()).and_then(|w| w.try_into().ok());
+
+    Ok(solution.and_then(|s| Some(Params { noun: narrow(&s, noun)?, verb: narrow(&s, verb)? })))
+}
+
+#[test]
+fn symbolic_matches_brute_force() {
+    let (symbolic, brute_force) = (find_noun_verb().unwrap().unwrap(), brute_force_noun_verb().unwrap());
+    assert_eq!((symbolic.noun, symbolic.verb), (brute_force.noun, brute_force.verb));
+}
+
+#[test]
+fn result_depends_on_noun_and_verb() {
+    use aoc2019::intcode_full;
+    use aoc2019::taint::{Tracker, Source};
+
+    let mut tracker = Tracker::new(intcode_full::State::new_from_str(INPUT, std::iter::empty()));
+    tracker.taint_cell(1usize.into());
+    tracker.taint_cell(2usize.into());
+    tracker.run_to_yield();
+
+    let sources: Vec<_> = tracker.read(&0usize.into()).sources.into_iter().collect();
+    assert_eq!(sources, [Source::Cell(1usize.into()), Source::Cell(2usize.into())]);
+}
+
 fn part_two() {
-    let solution = find_noun_verb().expect("Solution exists.");
+    let solution = find_noun_verb().expect("Program is linear in noun and verb.").expect("Solution exists.");
     println!("{}", 100 * solution.noun + solution.verb);
 }
 
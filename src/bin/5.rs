@@ -3,6 +3,9 @@ use std::convert::TryInto;
 use smallvec::SmallVec;
 use boolinator::Boolinator;
 
+#[cfg(test)]
+use aoc2019::difftest::{self, Case, Outcome, Reason, DAY_5};
+
 const INPUT: &'static str = include_str!("inputs/5.txt");
 
 const MAX_PARAMS: usize = 3;
@@ -230,6 +233,32 @@ fn examples() {
     assert_eq!(outputs(&larger_ex, 137), [1001]);
 }
 
+#[cfg(test)]
+fn run_case(case: &Case) -> Outcome {
+    let mut state = State::new_from_str(&case.program_str(), case.inputs[0]);
+
+    let mut reason = Reason::Halt;
+    while !state.halted {
+        if state.step().is_none() {
+            reason = Reason::Fault;
+            break;
+        }
+    }
+
+    Outcome {
+        outputs: state.outputs.to_vec(),
+        memory: state.memory,
+        reason
+    }
+}
+
+#[test]
+fn differential() {
+    difftest::check(&DAY_5, 0..300, &[("bin/5", &run_case),
+                                       ("intcode", &difftest::run_intcode),
+                                       ("intcode_full", &difftest::run_intcode_full)]);
+}
+
 fn part_one() {
     println!("{:?}", outputs(INPUT, 1).last().expect("Exists."));
 }
@@ -1,7 +1,10 @@
 use smallvec::SmallVec;
 
+use aoc2019::batch;
 use aoc2019::permutations::Permutations;
 use aoc2019::intcode::{State, Word, YieldReason};
+#[cfg(test)]
+use aoc2019::{intcode_full, driver::{Executor, Machine}};
 
 const INPUT: &'static str = include_str!("inputs/7.txt");
 
@@ -65,20 +68,21 @@ fn feedforward(s: &str, phase_seq: PhaseSeq) -> Word {
 }
 
 fn solve_part_one(s: &str) -> (Word, PhaseSeq) {
-    perms()
-        .map(|perm| map_perm(perm, |x| x as Word))
-        .map(|ps| {
-            let output = ps.into_iter().fold(0, |input: Word, &phase_setting| run_amplifier(s, phase_setting, input));
-            (output, ps)
-        })
-        .max()
-        .expect("Solution exists!")
+    let phase_seqs: Vec<_> = perms().map(|perm| map_perm(perm, |x| x as Word)).collect();
+
+    let outputs = batch::map(&phase_seqs, |&ps| {
+        let output = ps.iter().fold(0, |input: Word, &phase_setting| run_amplifier(s, phase_setting, input));
+        (output, ps)
+    });
+
+    outputs.into_iter().max().expect("Solution exists!")
 }
 
 fn solve_part_two(s: &str) -> (Word, PhaseSeq) {
-    perms()
-        .map(|perm| map_perm(perm, |x| (x + 5) as Word))
-        .map(|ps| (feedforward(s, ps), ps))
+    let phase_seqs: Vec<_> = perms().map(|perm| map_perm(perm, |x| (x + 5) as Word)).collect();
+
+    batch::map(&phase_seqs, |&ps| (feedforward(s, ps), ps))
+        .into_iter()
         .max()
         .expect("Solution exists!")
 }
@@ -95,12 +99,84 @@ fn examples_part_one() {
     assert_eq!(solve_part_one(&ex3), (65210, [1, 0, 4, 3, 2]));
 }
 
+#[cfg(test)]
+fn feedback_async(s: &str, phase_seq: PhaseSeq) -> Word {
+    let amps: Vec<_> = phase_seq.iter()
+        .map(|&phase_setting| Machine::new(intcode_full::State::new_from_str(s, [phase_setting].iter().cloned())))
+        .collect();
+
+    amps[0].supply(0.into());
+
+    let mut executor = Executor::new();
+    let signals: Vec<_> = (0..STAGES)
+        .map(|i| {
+            let (src, dest) = (amps[i].clone(), amps[(i + 1) % STAGES].clone());
+            executor.spawn(async move {
+                let mut last = None;
+                while let Ok(signal) = src.read().await {
+                    last = Some(signal.clone());
+                    dest.write(signal).await;
+                }
+
+                last
+            })
+        })
+        .collect();
+
+    executor.run().expect("Amplifiers do not deadlock!");
+
+    let signal = signals[STAGES - 1].take().flatten().expect("Last amplifier produced a signal!");
+    intcode_full::word_narrow(signal).expect("Signal fits!")
+}
+
 #[test]
 fn examples_part_two() {
     let ex1 = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
     assert_eq!(solve_part_two(&ex1), (139629729, [9, 8, 7, 6, 5]));
 }
 
+#[test]
+fn feedback_async_matches() {
+    let ex1 = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    assert_eq!(feedback_async(ex1, [9, 8, 7, 6, 5]), 139629729);
+
+    let (signal, phase_seq) = solve_part_two(INPUT);
+    assert_eq!(feedback_async(INPUT, phase_seq), signal);
+}
+
+#[cfg(test)]
+fn signal_sources(s: &str, phase_seq: PhaseSeq, control: bool) -> Vec<usize> {
+    use aoc2019::taint::{Tracker, Tainted, Source};
+
+    let tagged = |value: Word, i: usize| Tainted { value: value.into(), sources: std::iter::once(Source::Input(i)).collect() };
+    let mut signal = tagged(0, STAGES);
+
+    for (i, &phase_setting) in phase_seq.iter().enumerate() {
+        let mut amp = Tracker::new(intcode_full::State::new_from_str(s, std::iter::empty()));
+        if control {
+            amp.through_control_flow();
+        }
+
+        amp.supply_tainted(tagged(phase_setting, i));
+        amp.supply_tainted(signal);
+        signal = amp.run_to_yield().0.pop().expect("Amplifier produced a signal!");
+    }
+
+    signal.sources.into_iter().filter_map(|s| match s {
+        Source::Input(i) => Some(i),
+        Source::Cell(_) => None
+    }).collect()
+}
+
+// Phase settings only select which routine an amplifier runs, so they reach the signal through
+// branches alone.
+#[test]
+fn every_phase_reaches_the_signal() {
+    let (_, phase_seq) = solve_part_one(INPUT);
+    assert_eq!(signal_sources(INPUT, phase_seq, false), [5]);
+    assert_eq!(signal_sources(INPUT, phase_seq, true), [0, 1, 2, 3, 4, 5]);
+}
+
 fn part_one() {
     let (signal, _) = solve_part_one(INPUT);
     println!("{}", signal);
@@ -17,3 +17,17 @@ fn main() {
     part_one();
     part_two();
 }
+
+#[test]
+fn fits_in_i64() {
+    use aoc2019::intcode_full::{Semantics, YieldReason};
+
+    for &mode in [1isize, 2].iter() {
+        let mut state = State::new_from_str(INPUT, [mode].iter().cloned());
+        state.set_semantics(Semantics::TrapI64).expect("Program fits in i64.");
+
+        let (outputs, reason) = state.run_to_yield();
+        assert_eq!(reason, YieldReason::Halt);
+        assert_eq!(outputs, State::outputs(INPUT, [mode].iter().cloned()));
+    }
+}
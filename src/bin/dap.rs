@@ -0,0 +1,8 @@
+use std::io;
+
+use aoc2019::dap::{Adapter, serve};
+
+// Speaks the Debug Adapter Protocol on stdin/stdout; the program comes from the launch request.
+fn main() -> io::Result<()> {
+    serve(&mut Adapter::new(), io::stdin(), io::stdout())
+}
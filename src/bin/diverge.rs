@@ -0,0 +1,46 @@
+use std::env;
+use std::fs::File;
+use std::iter::empty;
+
+use aoc2019::intcode_full::State;
+use aoc2019::diverge::{first_divergence, Comparison};
+use aoc2019::cli::{usage, parse_inputs, apply_patches};
+
+const USAGE: &str = "Usage: diverge <program> [--limit <steps>] [--patch <address>=<value>]... <inputs> vs [--patch <address>=<value>]... <inputs>";
+const LIMIT: usize = 10_000_000;
+
+fn side(start: &State, args: &[String]) -> State {
+    let mut state = start.clone();
+
+    match apply_patches(&mut state, args) {
+        Some([inputs]) => parse_inputs(inputs).unwrap_or_else(|| usage(USAGE)).into_iter().for_each(|i| state.supply_word(i)),
+        _ => usage(USAGE)
+    }
+
+    state
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = args.first().unwrap_or_else(|| usage(USAGE));
+
+    let mut rest = &args[1..];
+    let mut limit = LIMIT;
+    if rest.first().map(String::as_str) == Some("--limit") {
+        limit = rest.get(1).and_then(|l| l.parse().ok()).unwrap_or_else(|| usage(USAGE));
+        rest = &rest[2..];
+    }
+
+    let split = rest.iter().position(|a| a == "vs").unwrap_or_else(|| usage(USAGE));
+
+    let file = File::open(path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e));
+    let start = State::load(file, empty()).unwrap_or_else(|e| panic!("Cannot load {}: {}", path, e));
+
+    let (left, right) = (side(&start, &rest[..split]), side(&start, &rest[split + 1..]));
+
+    match first_divergence(left, right, limit) {
+        Comparison::Diverged(divergence) => print!("{}", divergence),
+        Comparison::Yielded { step, reason } => println!("no divergence, both yield {:?} at step {}", reason, step),
+        Comparison::Exhausted => println!("no divergence within {} steps", limit)
+    }
+}
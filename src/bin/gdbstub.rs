@@ -0,0 +1,34 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use std::iter::empty;
+use std::net::TcpListener;
+
+use aoc2019::cli::usage;
+use aoc2019::intcode_full::State;
+use aoc2019::gdbstub::{Session, serve};
+
+const USAGE: &str = "Usage: gdbstub <program> (--tcp <port> | --stdio)";
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = args.first().unwrap_or_else(|| usage(USAGE));
+
+    let file = File::open(path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e));
+    let state = State::load(file, empty()).unwrap_or_else(|e| panic!("Cannot load {}: {}", path, e));
+    let mut session = Session::new(state);
+
+    match args.get(1..).unwrap_or(&[]) {
+        [mode] if mode == "--stdio" => serve(&mut session, io::stdin(), io::stdout()),
+        [mode, port] if mode == "--tcp" => {
+            let port: u16 = port.parse().unwrap_or_else(|_| usage(USAGE));
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("Listening on 127.0.0.1:{}, use `target remote :{}`", port, port);
+
+            let (stream, peer) = listener.accept()?;
+            eprintln!("Connection from {}", peer);
+            serve(&mut session, stream.try_clone()?, stream)
+        },
+        _ => usage(USAGE)
+    }
+}
@@ -0,0 +1,38 @@
+use std::env;
+use std::fs;
+use std::process::exit;
+
+use aoc2019::lint::{lint_str, Severity};
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: lint <program>...");
+        exit(2);
+    }
+
+    let mut errors = 0;
+
+    for path in paths.iter() {
+        let source = fs::read_to_string(path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e));
+
+        let diagnostics = match lint_str(&source) {
+            Some(diagnostics) => diagnostics,
+            None => {
+                println!("{}: error: not an Intcode program", path);
+                errors += 1;
+                continue;
+            }
+        };
+
+        for diagnostic in diagnostics.iter() {
+            println!("{}:{}", path, diagnostic);
+        }
+
+        errors += diagnostics.iter().filter(|d| d.issue.severity() == Severity::Error).count();
+    }
+
+    if errors > 0 {
+        exit(1);
+    }
+}
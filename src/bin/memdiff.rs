@@ -0,0 +1,43 @@
+use std::env;
+use std::fs::File;
+use std::iter::empty;
+
+use aoc2019::intcode_full::{State, Word};
+use aoc2019::memdiff::{advance, diff};
+use aoc2019::cli::{usage, parse_inputs, apply_patches};
+
+const USAGE: &str = "Usage: memdiff <program> [--patch <address>=<value>]... <inputs> (then|vs) <inputs>";
+
+fn run(state: &mut State, inputs: &[Word], name: &str) {
+    let reason = advance(state, inputs);
+    println!("{}: {:?} with {} outputs", name, reason, state.pending_outputs().len());
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = args.first().unwrap_or_else(|| usage(USAGE));
+
+    let file = File::open(path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e));
+    let mut start = State::load(file, empty()).unwrap_or_else(|e| panic!("Cannot load {}: {}", path, e));
+
+    let (first, mode, second) = match apply_patches(&mut start, &args[1..]).unwrap_or_else(|| usage(USAGE)) {
+        [first, mode, second] => (first, mode.as_str(), second),
+        _ => usage(USAGE)
+    };
+
+    let first = parse_inputs(first).unwrap_or_else(|| usage(USAGE));
+    let second = parse_inputs(second).unwrap_or_else(|| usage(USAGE));
+
+    let mut a = start.clone();
+    run(&mut a, &first, "a");
+
+    let mut b = match mode {
+        "then" => a.clone(),
+        "vs" => start,
+        _ => usage(USAGE)
+    };
+
+    run(&mut b, &second, "b");
+
+    print!("{}", diff(&a, &b));
+}
@@ -0,0 +1,49 @@
+use std::env;
+use std::fs::File;
+use std::iter::empty;
+use std::process::exit;
+
+use itertools::join;
+
+use aoc2019::intcode_full::State;
+use aoc2019::minimize::{Failure, minimize, run};
+use aoc2019::cli::{usage, parse_inputs};
+
+const USAGE: &str = "Usage: minimize <program> [--budget <steps>] (<inputs> | @<file>) (fault | output <value> | budget)";
+const BUDGET: usize = 1_000_000;
+
+fn parse_failure(args: &[String]) -> Option<Failure> {
+    match args {
+        [kind] if kind == "fault" => Some(Failure::Fault),
+        [kind] if kind == "budget" => Some(Failure::BudgetExhausted),
+        [kind, value] if kind == "output" => value.parse().ok().map(Failure::Output),
+        _ => None
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = args.first().unwrap_or_else(|| usage(USAGE));
+
+    let mut rest = &args[1..];
+    let mut budget = BUDGET;
+    if rest.first().map(String::as_str) == Some("--budget") {
+        budget = rest.get(1).and_then(|b| b.parse().ok()).unwrap_or_else(|| usage(USAGE));
+        rest = &rest[2..];
+    }
+
+    let inputs = rest.first().and_then(|i| parse_inputs(i)).unwrap_or_else(|| usage(USAGE));
+    let failure = parse_failure(&rest[1..]).unwrap_or_else(|| usage(USAGE));
+
+    let file = File::open(path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e));
+    let start = State::load(file, empty()).unwrap_or_else(|e| panic!("Cannot load {}: {}", path, e));
+
+    if !failure.matches(&run(&start, &inputs, budget)) {
+        eprintln!("The {} inputs do not produce {}.", inputs.len(), failure);
+        exit(1);
+    }
+
+    let minimal = minimize(&start, &inputs, &failure, budget);
+    eprintln!("Reduced {} inputs to {}.", inputs.len(), minimal.len());
+    println!("{}", join(&minimal, ","));
+}
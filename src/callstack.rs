@@ -0,0 +1,58 @@
+use std::fmt;
+
+use crate::intcode_full::{Opcode, Pos, Word};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Frame {
+    pub call_site: Pos,
+    pub entry: Pos,
+    pub return_address: Pos,
+    pub base: Word
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct CallStack {
+    jump: Option<(Pos, Pos, Pos)>,
+    frames: Vec<Frame>
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} called from {}, returns to {}, frame base {}", self.entry, self.call_site, self.return_address, self.base)
+    }
+}
+
+// A taken jump whose target starts with a positive `AdjRelBase` is treated as a call; the frame
+// lives until `rel_base` drops below its base again, which is what compiled epilogues do.
+impl CallStack {
+    pub(crate) fn observe(&mut self, opcode: &Opcode, ip: Pos, len: usize, rel_base: &Word, next_ip: &Pos, next_rel_base: &Word) {
+        let jump = self.jump.take();
+
+        match opcode {
+            Opcode::AdjRelBase if next_rel_base > rel_base => {
+                if let Some((call_site, return_address, _)) = jump.filter(|(_, _, target)| target == &ip) {
+                    self.frames.push(Frame { call_site, entry: ip, return_address, base: next_rel_base.clone() });
+                }
+            },
+
+            Opcode::AdjRelBase => {
+                while self.frames.last().is_some_and(|frame| &frame.base > next_rel_base) {
+                    self.frames.pop();
+                }
+            },
+
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Extension(_) => {
+                let fallthrough = &ip + len;
+                if next_ip != &fallthrough {
+                    self.jump = Some((ip, fallthrough, next_ip.clone()));
+                }
+            },
+
+            _ => ()
+        }
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item=&Frame> {
+        self.frames.iter().rev()
+    }
+}
@@ -0,0 +1,50 @@
+use std::fs::File;
+use std::process::exit;
+
+use crate::intcode_full::{State, Word, load_words};
+
+// Argument handling shared by the debugging bins.
+pub fn usage(text: &str) -> ! {
+    eprintln!("{}", text);
+    exit(2);
+}
+
+// `-` for no inputs, a comma separated list, or `@<file>` in the program format so comments are allowed.
+pub fn parse_inputs(s: &str) -> Option<Vec<Word>> {
+    match s {
+        "-" => Some(Vec::new()),
+        _ if s.starts_with('@') => load_words(File::open(&s[1..]).ok()?).ok(),
+        _ => load_words(s.as_bytes()).ok()
+    }
+}
+
+pub fn parse_patch(s: &str) -> Option<(Word, Word)> {
+    let mut parts = s.splitn(2, '=');
+    Some((parts.next()?.trim().parse().ok()?, parts.next()?.trim().parse().ok()?))
+}
+
+// Applies leading `--patch <address>=<value>` pairs and returns the arguments after them.
+pub fn apply_patches<'a>(state: &mut State, mut args: &'a [String]) -> Option<&'a [String]> {
+    while args.first().map(String::as_str) == Some("--patch") {
+        let (pos, value) = parse_patch(args.get(1)?)?;
+        state.try_write_memory(&pos, value).ok()?;
+        args = &args[2..];
+    }
+
+    Some(args)
+}
+
+#[test]
+fn parses_inputs_and_patches() {
+    assert_eq!(parse_inputs("-"), Some(vec![]));
+    assert_eq!(parse_inputs("1, -2,3"), Some(vec![1.into(), (-2).into(), 3.into()]));
+    assert_eq!(parse_inputs("1,x"), None);
+
+    let args: Vec<String> = ["--patch", "1=5", "--patch", "0=7", "-"].iter().map(|a| a.to_string()).collect();
+    let mut state = State::new_from_str("99,0", std::iter::empty());
+    assert_eq!(apply_patches(&mut state, &args), Some(&args[4..]));
+    assert_eq!(state.read_range(&0usize.into(), 2), vec![7.into(), 5.into()]);
+
+    let negative: Vec<String> = ["--patch", "-1=5"].iter().map(|a| a.to_string()).collect();
+    assert_eq!(apply_patches(&mut state, &negative), None);
+}
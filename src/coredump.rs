@@ -0,0 +1,169 @@
+use std::env;
+use std::fs;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use itertools::join;
+use num_traits::cast::ToPrimitive;
+
+use crate::disasm::{Line, disassemble};
+use crate::callstack::Frame;
+use crate::intcode_full::{State, YieldReason, WriteFault, Pos, Word};
+
+pub const HISTORY: usize = 32;
+// Dumps always go to stderr; they are also written as files into this directory when it is set.
+pub const CORE_DIR: &str = "INTCODE_CORE_DIR";
+const BACKTRACK: usize = 16;
+const LOOKAHEAD: usize = 4;
+const STACK: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct CoreDump {
+    pub reason: YieldReason,
+    pub ip: Pos,
+    pub rel_base: Word,
+    pub write_fault: Option<WriteFault>,
+    pub backtrace: Vec<Frame>,
+    pub code: Vec<Line>,
+    pub history: Vec<Line>,
+    pub stack: Vec<(Pos, Word)>,
+    pub inputs: Vec<Word>,
+    pub outputs: Vec<Word>
+}
+
+// Intcode cannot be decoded backwards, so the listing starts at the earliest recently executed
+// instruction shortly before `ip` and falls back to `ip` itself when the sweep would skip over it.
+fn code_around(state: &State) -> Vec<Line> {
+    let ip = state.ip();
+    let lowest = ip.to_usize().map_or(0, |ip| ip.saturating_sub(BACKTRACK));
+
+    let start = state.history()
+        .map(|e| &e.ip)
+        .filter(|&a| a <= ip && a.to_usize().is_some_and(|a| a >= lowest))
+        .min()
+        .unwrap_or(ip);
+
+    let mut lines = Vec::new();
+    let mut address = start.clone();
+
+    while &address < ip {
+        let line = Line::at(state, &address);
+        if &line.end() > ip {
+            break;
+        }
+
+        address = line.end();
+        lines.push(line);
+    }
+
+    lines.extend(disassemble(state, ip, LOOKAHEAD));
+    lines
+}
+
+fn stack_window(state: &State) -> Vec<(Pos, Word)> {
+    let base = state.rel_base();
+    (-(STACK as isize)..=STACK as isize)
+        .filter_map(|offset| (base + offset).to_biguint())
+        .map(|pos| {
+            let value = state.read(&pos);
+            (pos, value)
+        })
+        .collect()
+}
+
+impl CoreDump {
+    pub fn capture(state: &State, reason: YieldReason, outputs: &[Word]) -> Self {
+        CoreDump {
+            reason,
+            ip: state.ip().clone(),
+            rel_base: state.rel_base().clone(),
+            write_fault: state.write_fault().cloned(),
+            backtrace: state.backtrace(),
+            code: code_around(state),
+            history: state.history().map(|e| Line::executed(state, e)).collect(),
+            stack: stack_window(state),
+            inputs: state.pending_inputs(),
+            outputs: outputs.iter().chain(state.pending_outputs()).cloned().collect()
+        }
+    }
+
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for CoreDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Intcode core dump: {:?}", self.reason)?;
+        writeln!(f, "ip: {}", self.ip)?;
+        writeln!(f, "rel_base: {}", self.rel_base)?;
+
+        if let Some(fault) = &self.write_fault {
+            writeln!(f, "write fault: {} wrote to protected address {}", fault.ip, fault.target)?;
+        }
+
+        if !self.backtrace.is_empty() {
+            writeln!(f, "\nbacktrace:")?;
+            for (depth, frame) in self.backtrace.iter().enumerate() {
+                writeln!(f, "  #{} {}", depth, frame)?;
+            }
+        }
+
+        writeln!(f, "\ncode:")?;
+        for line in self.code.iter() {
+            let marker = if line.address == self.ip { "=>" } else { "  " };
+            writeln!(f, "{}{}", marker, line)?;
+        }
+
+        writeln!(f, "\nlast {} executed:", self.history.len())?;
+        for line in self.history.iter() {
+            writeln!(f, "  {}", line)?;
+        }
+
+        writeln!(f, "\nstack:")?;
+        for (pos, value) in self.stack.iter() {
+            let marker = if Word::from(pos.clone()) == self.rel_base { "rb" } else { "  " };
+            writeln!(f, "{}{:>8}  {}", marker, pos, value)?;
+        }
+
+        writeln!(f, "\ninputs: [{}]", join(&self.inputs, ","))?;
+        writeln!(f, "outputs: [{}]", join(&self.outputs, ","))
+    }
+}
+
+pub fn crash<P: AsRef<Path>>(state: &State, reason: YieldReason, outputs: &[Word], name: P) -> ! {
+    let dump = CoreDump::capture(state, reason, outputs);
+    eprint!("{}", dump);
+
+    let path = match env::var_os(CORE_DIR) {
+        Some(dir) => Path::new(&dir).join(name),
+        None => panic!("Incorrect IntCode program! Set {} to also write the core dump to a file.", CORE_DIR)
+    };
+
+    match dump.write_to(&path) {
+        Ok(()) => panic!("Incorrect IntCode program! Core dump written to {}.", path.display()),
+        Err(e) => panic!("Incorrect IntCode program! Cannot write core dump: {}", e)
+    }
+}
+
+#[test]
+fn dump_on_fault() {
+    let mut state = State::new_from_str("109,10,3,20,1001,20,1,21,4,21,42", [5isize, 6].iter().cloned());
+    state.keep_history(2);
+
+    let (outputs, reason) = state.run_to_yield();
+    assert_eq!(reason, YieldReason::IncorrectOpcode);
+
+    let dump = CoreDump::capture(&state, reason, &outputs);
+    assert_eq!(dump.ip, 10usize.into());
+    assert_eq!(dump.outputs, vec![6.into()]);
+    assert_eq!(dump.inputs, vec![6.into()]);
+    assert_eq!(dump.history.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(), ["add [20], 1, [21]", "out [21]"]);
+    assert_eq!(dump.code.iter().map(|l| l.address.to_usize().unwrap()).collect::<Vec<_>>(), [4, 8, 10, 11, 12, 13]);
+    assert_eq!(dump.stack.len(), 2 * STACK + 1);
+
+    let text = dump.to_string();
+    assert!(text.contains("=>      10  42"));
+    assert!(text.contains("rb      10  42"));
+}
@@ -0,0 +1,547 @@
+use std::fs::File;
+use std::io::{self, Read, Write, BufRead, BufReader};
+use std::collections::{BTreeMap, BTreeSet};
+
+use itertools::join;
+use boolinator::Boolinator;
+use num_traits::cast::ToPrimitive;
+
+use crate::json::Json;
+use crate::disasm::Line;
+use crate::intcode_full::{State, YieldReason, Pos, Word, load_labelled};
+
+const THREAD: usize = 1;
+const PAGE: usize = 64;
+
+const REGISTERS: usize = 1;
+const QUEUES: usize = 2;
+const MEMORY: usize = 3;
+const PAGES: usize = 1000;
+// A continue that runs this long stops as if paused, so a looping program cannot wedge the adapter.
+const BUDGET: usize = 10_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Resume {
+    Continue,
+    StepIn,
+    Over,
+    Out
+}
+
+enum Stop {
+    Breakpoint,
+    Step,
+    Budget,
+    Yield(YieldReason)
+}
+
+#[derive(Default)]
+pub struct Adapter {
+    state: Option<State>,
+    labels: BTreeMap<String, usize>,
+    instruction_breakpoints: BTreeSet<Pos>,
+    function_breakpoints: BTreeSet<Pos>,
+    outputs: Vec<Word>,
+    pages: BTreeSet<usize>,
+    stop_on_entry: bool,
+    configured: bool,
+    done: bool,
+    seq: usize,
+    events: Vec<(String, Json)>
+}
+
+fn parse_word(json: &Json) -> Option<Word> {
+    match json {
+        Json::String(s) => s.trim().parse().ok(),
+        _ => json.as_i64().map(Word::from)
+    }
+}
+
+fn parse_address(s: &str) -> Option<Pos> {
+    s.trim().trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+fn items(args: &Json, key: &str) -> Vec<Json> {
+    args.get(key).and_then(Json::as_array).unwrap_or(&[]).to_vec()
+}
+
+fn variable(name: &str, value: String, reference: usize) -> Json {
+    Json::object(vec![("name", name.into()), ("value", value.into()), ("variablesReference", reference.into())])
+}
+
+impl Adapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn next_seq(&mut self) -> usize {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.events.push((event.to_string(), body));
+    }
+
+    fn state(&self) -> Result<&State, String> {
+        self.state.as_ref().ok_or_else(|| "no program launched".to_string())
+    }
+
+    fn location(&self, address: &Pos) -> String {
+        let label = address.to_usize().and_then(|a| {
+            self.labels.iter().filter(|&(_, &l)| l <= a).max_by_key(|&(_, &l)| l).map(|(name, &l)| (name, a - l))
+        });
+
+        match label {
+            Some((name, 0)) => name.clone(),
+            Some((name, offset)) => format!("{}+{}", name, offset),
+            None => address.to_string()
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<Pos> {
+        self.labels.get(name).map(|&a| a.into()).or_else(|| parse_address(name))
+    }
+
+    fn launch(&mut self, args: &Json) -> Result<Option<Json>, String> {
+        let source = match (args.get("source").and_then(Json::as_str), args.get("program").and_then(Json::as_str)) {
+            (Some(source), _) => source.to_string(),
+            (None, Some(path)) => {
+                let mut source = String::new();
+                File::open(path).and_then(|mut f| f.read_to_string(&mut source)).map_err(|e| format!("cannot read {}: {}", path, e))?;
+                source
+            },
+            (None, None) => return Err("launch needs a program or source".to_string())
+        };
+
+        let (words, labels) = load_labelled(source.as_bytes()).map_err(|e| e.to_string())?;
+        let inputs: Option<Vec<Word>> = items(args, "inputs").iter().map(parse_word).collect();
+
+        let mut state = State::from_words(words, std::iter::empty());
+        inputs.ok_or_else(|| "inputs must be integers".to_string())?.into_iter().for_each(|w| state.supply_word(w));
+        state.track_calls();
+
+        self.state = Some(state);
+        self.labels = labels;
+        self.stop_on_entry = args.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+        self.start();
+        Ok(None)
+    }
+
+    fn start(&mut self) {
+        if !self.configured || self.state.is_none() {
+            return;
+        }
+
+        if self.stop_on_entry {
+            self.event("stopped", Json::object(vec![("reason", "entry".into()), ("threadId", THREAD.into())]));
+        } else {
+            self.resume(Resume::Continue);
+        }
+    }
+
+    fn resume(&mut self, mode: Resume) {
+        let state = match self.state.as_mut() {
+            Some(state) => state,
+            None => return
+        };
+
+        let depth = state.backtrace().len();
+        let mut first = true;
+        let mut steps = 0;
+
+        let stop = loop {
+            if !first {
+                if self.instruction_breakpoints.contains(state.ip()) || self.function_breakpoints.contains(state.ip()) {
+                    break Stop::Breakpoint;
+                }
+
+                let stepped = match mode {
+                    Resume::Continue => false,
+                    Resume::StepIn => true,
+                    Resume::Over => state.backtrace().len() <= depth,
+                    Resume::Out => state.backtrace().len() < depth
+                };
+
+                if stepped {
+                    break Stop::Step;
+                }
+            }
+
+            if steps == BUDGET {
+                break Stop::Budget;
+            }
+
+            first = false;
+            steps += 1;
+
+            if let Err(reason) = state.step() {
+                break Stop::Yield(reason);
+            }
+        };
+
+        let mut outputs = Vec::new();
+        while let Some(output) = state.take_output() {
+            outputs.push(output);
+        }
+
+        for output in outputs {
+            self.event("output", Json::object(vec![("category", "stdout".into()), ("output", format!("{}\n", output).into())]));
+            self.outputs.push(output);
+        }
+
+        let (reason, description) = match stop {
+            Stop::Breakpoint => ("breakpoint", None),
+            Stop::Step => ("step", None),
+            Stop::Budget => ("pause", Some(format!("Still running after {} steps", BUDGET))),
+            Stop::Yield(YieldReason::Halt) => {
+                self.event("exited", Json::object(vec![("exitCode", 0usize.into())]));
+                self.event("terminated", Json::object(vec![]));
+                return;
+            },
+            Stop::Yield(YieldReason::WaitInput) => ("pause", Some("Waiting for input, evaluate `input <w1,w2,...>`".to_string())),
+            Stop::Yield(reason) => ("exception", Some(format!("{:?}", reason)))
+        };
+
+        let mut body = vec![("reason", reason.into()), ("threadId", THREAD.into())];
+        if let Some(description) = description {
+            body.push(("description", description.into()));
+        }
+
+        self.event("stopped", Json::object(body));
+    }
+
+    fn function_breakpoints(&mut self, args: &Json) -> Result<Option<Json>, String> {
+        self.function_breakpoints.clear();
+
+        let mut replies = Vec::new();
+        for bp in items(args, "breakpoints") {
+            let name = bp.get("name").and_then(Json::as_str).unwrap_or("");
+            replies.push(match self.resolve(name) {
+                Some(address) => {
+                    let reply = Json::object(vec![("verified", true.into()), ("instructionReference", address.to_string().into())]);
+                    self.function_breakpoints.insert(address);
+                    reply
+                },
+                None => Json::object(vec![("verified", false.into()), ("message", format!("unknown label {}", name).into())])
+            });
+        }
+
+        Ok(Some(Json::object(vec![("breakpoints", replies.into())])))
+    }
+
+    fn instruction_breakpoints(&mut self, args: &Json) -> Result<Option<Json>, String> {
+        self.instruction_breakpoints.clear();
+
+        let mut replies = Vec::new();
+        for bp in items(args, "breakpoints") {
+            let reference = bp.get("instructionReference").and_then(Json::as_str).and_then(parse_address);
+            let offset = bp.get("offset").and_then(Json::as_i64).unwrap_or(0);
+            let address = reference.and_then(|r| (Word::from(r) + offset).to_biguint());
+
+            replies.push(match address {
+                Some(address) => {
+                    let reply = Json::object(vec![("verified", true.into()), ("instructionReference", address.to_string().into())]);
+                    self.instruction_breakpoints.insert(address);
+                    reply
+                },
+                None => Json::object(vec![("verified", false.into()), ("message", "bad instruction reference".into())])
+            });
+        }
+
+        Ok(Some(Json::object(vec![("breakpoints", replies.into())])))
+    }
+
+    fn stack_trace(&self) -> Result<Option<Json>, String> {
+        let state = self.state()?;
+
+        let mut addresses = vec![state.ip().clone()];
+        addresses.extend(state.backtrace().into_iter().map(|frame| frame.call_site));
+
+        let frames: Vec<Json> = addresses.iter().enumerate().map(|(id, address)| {
+            let name = format!("{}: {}", self.location(address), Line::at(state, address).text);
+            Json::object(vec![
+                ("id", id.into()), ("name", name.into()), ("line", 0usize.into()), ("column", 0usize.into()),
+                ("instructionPointerReference", address.to_string().into())
+            ])
+        }).collect();
+
+        let total = frames.len();
+        Ok(Some(Json::object(vec![("stackFrames", frames.into()), ("totalFrames", total.into())])))
+    }
+
+    fn scopes(&self) -> Result<Option<Json>, String> {
+        let scope = |name: &str, reference: usize, expensive: bool| Json::object(vec![
+            ("name", name.into()), ("variablesReference", reference.into()), ("expensive", expensive.into())
+        ]);
+
+        let scopes = vec![scope("Registers", REGISTERS, false), scope("I/O", QUEUES, false), scope("Memory", MEMORY, true)];
+        Ok(Some(Json::object(vec![("scopes", scopes.into())])))
+    }
+
+    fn variables(&mut self, args: &Json) -> Result<Option<Json>, String> {
+        let reference = args.get("variablesReference").and_then(Json::as_i64).unwrap_or(0);
+        let reference = reference.to_usize().ok_or_else(|| format!("unknown variables reference {}", reference))?;
+        let state = self.state.as_ref().ok_or_else(|| "no program launched".to_string())?;
+
+        let variables = match reference {
+            REGISTERS => vec![
+                variable("ip", state.ip().to_string(), 0),
+                variable("rel_base", state.rel_base().to_string(), 0),
+                variable("halted", state.is_halted().to_string(), 0)
+            ],
+            QUEUES => vec![
+                variable("inputs", format!("[{}]", join(state.pending_inputs(), ",")), 0),
+                variable("outputs", format!("[{}]", join(&self.outputs, ",")), 0)
+            ],
+            MEMORY => {
+                self.pages = state.cells().filter_map(|(pos, _)| pos.to_usize()).map(|a| a / PAGE).collect();
+                self.pages.iter()
+                    .map(|p| variable(&format!("{}..{}", p * PAGE, (p + 1) * PAGE), String::new(), PAGES + p))
+                    .collect()
+            },
+            r if r >= PAGES && self.pages.contains(&(r - PAGES)) => (0..PAGE).map(|i| {
+                let address = (r - PAGES) * PAGE + i;
+                variable(&format!("[{}]", address), state.read(&address.into()).to_string(), 0)
+            }).collect(),
+            _ => return Err(format!("unknown variables reference {}", reference))
+        };
+
+        Ok(Some(Json::object(vec![("variables", variables.into())])))
+    }
+
+    fn evaluate(&mut self, args: &Json) -> Result<Option<Json>, String> {
+        let expression = args.get("expression").and_then(Json::as_str).unwrap_or("").trim().to_string();
+
+        let result = match expression.strip_prefix("input ") {
+            Some(words) => {
+                let words: Option<Vec<Word>> = words.split(',').map(|w| w.trim().parse().ok()).collect();
+                let words = words.ok_or_else(|| "inputs must be integers".to_string())?;
+                let count = words.len();
+
+                let state = self.state.as_mut().ok_or_else(|| "no program launched".to_string())?;
+                words.into_iter().for_each(|w| state.supply_word(w));
+                format!("queued {} inputs", count)
+            },
+            None => {
+                let address = self.resolve(&expression).ok_or_else(|| format!("cannot evaluate {}", expression))?;
+                self.state()?.read(&address).to_string()
+            }
+        };
+
+        Ok(Some(Json::object(vec![("result", result.into()), ("variablesReference", 0usize.into())])))
+    }
+
+    fn dispatch(&mut self, command: &str, args: &Json) -> Result<Option<Json>, String> {
+        match command {
+            "initialize" => {
+                self.event("initialized", Json::object(vec![]));
+                Ok(Some(Json::object(vec![
+                    ("supportsConfigurationDoneRequest", true.into()),
+                    ("supportsFunctionBreakpoints", true.into()),
+                    ("supportsInstructionBreakpoints", true.into())
+                ])))
+            },
+            "launch" => self.launch(args),
+            "configurationDone" => {
+                self.configured = true;
+                self.start();
+                Ok(None)
+            },
+            "setBreakpoints" => {
+                let unverified = Json::object(vec![("verified", false.into()), ("message", "use function or instruction breakpoints".into())]);
+                let replies = vec![unverified; items(args, "breakpoints").len()];
+                Ok(Some(Json::object(vec![("breakpoints", replies.into())])))
+            },
+            "setFunctionBreakpoints" => self.function_breakpoints(args),
+            "setInstructionBreakpoints" => self.instruction_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(None),
+            "threads" => {
+                let thread = Json::object(vec![("id", THREAD.into()), ("name", "intcode".into())]);
+                Ok(Some(Json::object(vec![("threads", vec![thread].into())])))
+            },
+            "stackTrace" => self.stack_trace(),
+            "scopes" => self.scopes(),
+            "variables" => self.variables(args),
+            "evaluate" => self.evaluate(args),
+            "continue" | "next" | "stepIn" | "stepOut" => {
+                self.state()?;
+                let mode = match command {
+                    "continue" => Resume::Continue,
+                    "next" => Resume::Over,
+                    "stepIn" => Resume::StepIn,
+                    _ => Resume::Out
+                };
+
+                self.resume(mode);
+                Ok((mode == Resume::Continue).as_some(Json::object(vec![("allThreadsContinued", true.into())])))
+            },
+            "pause" => Err(format!("pause is not supported, continue stops by itself after {} steps", BUDGET)),
+            "disconnect" | "terminate" => {
+                self.done = true;
+                Ok(None)
+            },
+            _ => Err(format!("unsupported command {}", command))
+        }
+    }
+
+    // Returns the response followed by the events the request caused.
+    pub fn handle(&mut self, request: &Json) -> Vec<Json> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("").to_string();
+        let request_seq = request.get("seq").and_then(Json::as_i64).unwrap_or(0);
+        let args = request.get("arguments").cloned().unwrap_or(Json::Null);
+
+        let result = self.dispatch(&command, &args);
+
+        let mut response = vec![
+            ("seq", self.next_seq().into()), ("type", "response".into()), ("request_seq", request_seq.into()),
+            ("success", result.is_ok().into()), ("command", command.into())
+        ];
+
+        match result {
+            Ok(Some(body)) => response.push(("body", body)),
+            Ok(None) => (),
+            Err(message) => response.push(("message", message.into()))
+        }
+
+        let mut messages = vec![Json::object(response)];
+        for (event, body) in std::mem::take(&mut self.events) {
+            let seq = self.next_seq();
+            messages.push(Json::object(vec![("seq", seq.into()), ("type", "event".into()), ("event", event.into()), ("body", body)]));
+        }
+
+        messages
+    }
+}
+
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn frame(message: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+}
+
+pub fn serve<R: Read, W: Write>(adapter: &mut Adapter, reader: R, mut writer: W) -> io::Result<()> {
+    let mut reader = BufReader::new(reader);
+
+    while !adapter.is_done() {
+        let message = match read_message(&mut reader)? {
+            Some(message) => message,
+            None => break
+        };
+
+        let request = Json::parse(&message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        for reply in adapter.handle(&request) {
+            writer.write_all(frame(&reply.to_string()).as_bytes())?;
+        }
+
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn recorded_session() {
+    let source = "# main:\n3,100,\n# loop:\n1001,100,-1,100,\n4,100,\n1005,100,2,\n99\n";
+    let launch = Json::object(vec![
+        ("seq", 2usize.into()), ("type", "request".into()), ("command", "launch".into()),
+        ("arguments", Json::object(vec![("source", source.into()), ("inputs", vec![Json::from(2usize)].into())]))
+    ]);
+
+    let requests = [
+        r#"{"seq":1,"type":"request","command":"initialize","arguments":{"adapterID":"intcode"}}"#.to_string(),
+        launch.to_string(),
+        r#"{"seq":3,"type":"request","command":"setFunctionBreakpoints","arguments":{"breakpoints":[{"name":"loop"},{"name":"nowhere"}]}}"#.to_string(),
+        r#"{"seq":4,"type":"request","command":"configurationDone"}"#.to_string(),
+        r#"{"seq":5,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#.to_string(),
+        r#"{"seq":6,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#.to_string(),
+        r#"{"seq":7,"type":"request","command":"next","arguments":{"threadId":1}}"#.to_string(),
+        r#"{"seq":8,"type":"request","command":"continue","arguments":{"threadId":1}}"#.to_string(),
+        r#"{"seq":9,"type":"request","command":"evaluate","arguments":{"expression":"[100]"}}"#.to_string(),
+        r#"{"seq":10,"type":"request","command":"setFunctionBreakpoints","arguments":{"breakpoints":[]}}"#.to_string(),
+        r#"{"seq":11,"type":"request","command":"continue","arguments":{"threadId":1}}"#.to_string(),
+        r#"{"seq":12,"type":"request","command":"variables","arguments":{"variablesReference":2}}"#.to_string(),
+        r#"{"seq":13,"type":"request","command":"disconnect"}"#.to_string()
+    ];
+
+    let expected = [
+        r#"{"seq":1,"type":"response","request_seq":1,"success":true,"command":"initialize","body":{"supportsConfigurationDoneRequest":true,"supportsFunctionBreakpoints":true,"supportsInstructionBreakpoints":true}}"#,
+        r#"{"seq":2,"type":"event","event":"initialized","body":{}}"#,
+        r#"{"seq":3,"type":"response","request_seq":2,"success":true,"command":"launch"}"#,
+        r#"{"seq":4,"type":"response","request_seq":3,"success":true,"command":"setFunctionBreakpoints","body":{"breakpoints":[{"verified":true,"instructionReference":"2"},{"verified":false,"message":"unknown label nowhere"}]}}"#,
+        r#"{"seq":5,"type":"response","request_seq":4,"success":true,"command":"configurationDone"}"#,
+        r#"{"seq":6,"type":"event","event":"stopped","body":{"reason":"breakpoint","threadId":1}}"#,
+        r#"{"seq":7,"type":"response","request_seq":5,"success":true,"command":"stackTrace","body":{"stackFrames":[{"id":0,"name":"loop: add [100], -1, [100]","line":0,"column":0,"instructionPointerReference":"2"}],"totalFrames":1}}"#,
+        r#"{"seq":8,"type":"response","request_seq":6,"success":true,"command":"variables","body":{"variables":[{"name":"ip","value":"2","variablesReference":0},{"name":"rel_base","value":"0","variablesReference":0},{"name":"halted","value":"false","variablesReference":0}]}}"#,
+        r#"{"seq":9,"type":"response","request_seq":7,"success":true,"command":"next"}"#,
+        r#"{"seq":10,"type":"event","event":"stopped","body":{"reason":"step","threadId":1}}"#,
+        r#"{"seq":11,"type":"response","request_seq":8,"success":true,"command":"continue","body":{"allThreadsContinued":true}}"#,
+        r#"{"seq":12,"type":"event","event":"output","body":{"category":"stdout","output":"1\n"}}"#,
+        r#"{"seq":13,"type":"event","event":"stopped","body":{"reason":"breakpoint","threadId":1}}"#,
+        r#"{"seq":14,"type":"response","request_seq":9,"success":true,"command":"evaluate","body":{"result":"1","variablesReference":0}}"#,
+        r#"{"seq":15,"type":"response","request_seq":10,"success":true,"command":"setFunctionBreakpoints","body":{"breakpoints":[]}}"#,
+        r#"{"seq":16,"type":"response","request_seq":11,"success":true,"command":"continue","body":{"allThreadsContinued":true}}"#,
+        r#"{"seq":17,"type":"event","event":"output","body":{"category":"stdout","output":"0\n"}}"#,
+        r#"{"seq":18,"type":"event","event":"exited","body":{"exitCode":0}}"#,
+        r#"{"seq":19,"type":"event","event":"terminated","body":{}}"#,
+        r#"{"seq":20,"type":"response","request_seq":12,"success":true,"command":"variables","body":{"variables":[{"name":"inputs","value":"[]","variablesReference":0},{"name":"outputs","value":"[1,0]","variablesReference":0}]}}"#,
+        r#"{"seq":21,"type":"response","request_seq":13,"success":true,"command":"disconnect"}"#
+    ];
+
+    let input: String = requests.iter().map(|r| frame(r)).collect();
+    let mut output = Vec::new();
+    serve(&mut Adapter::new(), input.as_bytes(), &mut output).unwrap();
+
+    let mut reader = BufReader::new(&output[..]);
+    let mut replies = Vec::new();
+    while let Some(message) = read_message(&mut reader).unwrap() {
+        replies.push(message);
+    }
+
+    assert_eq!(replies, expected);
+}
+
+#[test]
+fn rejects_unissued_references_and_pause() {
+    let mut adapter = Adapter::new();
+    let launch = Json::object(vec![("source", "1105,1,0".into())]);
+    adapter.dispatch("launch", &launch).unwrap();
+
+    let reference = |r: i64| Json::object(vec![("variablesReference", r.into())]);
+    assert!(adapter.dispatch("variables", &reference(-1)).is_err());
+    assert!(adapter.dispatch("variables", &reference(PAGES as i64 + 5)).is_err());
+
+    adapter.dispatch("variables", &reference(MEMORY as i64)).unwrap();
+    assert!(adapter.dispatch("variables", &reference(PAGES as i64)).is_ok());
+    assert!(adapter.dispatch("variables", &reference(PAGES as i64 + 5)).is_err());
+
+    assert!(adapter.dispatch("pause", &Json::Null).is_err());
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::any::Any;
+
+use num_traits::cast::ToPrimitive;
+
+use crate::rng::SplitMix;
+use crate::intcode_full::{Device, Pos, Word};
+
+// Counts executed instructions; writing sets the counter.
+#[derive(Clone, Default, Debug)]
+pub struct CycleCounter {
+    pub cycles: u64
+}
+
+// Every read draws a fresh value below `bound`; writing reseeds the generator.
+#[derive(Clone)]
+pub struct Random {
+    rng: SplitMix,
+    bound: usize
+}
+
+// A `width` x `height` grid of character codes, row by row; unset or non-ASCII cells show as spaces.
+#[derive(Clone, Debug)]
+pub struct CharDisplay {
+    width: usize,
+    cells: Vec<u8>
+}
+
+impl Device for CycleCounter {
+    fn read(&mut self, offset: &Pos) -> Word {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, _: &Pos, value: Word) {
+        self.cycles = value.to_u64().unwrap_or(0);
+    }
+
+    fn peek(&self, _: &Pos) -> Word {
+        self.cycles.into()
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    fn box_clone(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64, bound: usize) -> Self {
+        Random { rng: SplitMix::new(seed), bound }
+    }
+}
+
+impl Device for Random {
+    fn read(&mut self, _: &Pos) -> Word {
+        self.rng.below(self.bound).into()
+    }
+
+    fn write(&mut self, _: &Pos, value: Word) {
+        self.rng = SplitMix::new(value.to_u64().unwrap_or(0));
+    }
+
+    fn peek(&self, offset: &Pos) -> Word {
+        self.clone().read(offset)
+    }
+
+    fn box_clone(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CharDisplay {
+    pub fn new(width: usize, height: usize) -> Self {
+        CharDisplay { width, cells: vec![b' '; width * height] }
+    }
+
+    pub fn cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn row(&self, y: usize) -> String {
+        String::from_utf8_lossy(&self.cells[y * self.width..(y + 1) * self.width]).into_owned()
+    }
+}
+
+impl Device for CharDisplay {
+    fn read(&mut self, offset: &Pos) -> Word {
+        self.peek(offset)
+    }
+
+    fn write(&mut self, offset: &Pos, value: Word) {
+        let code = value.to_u8().filter(|c| c.is_ascii_graphic()).unwrap_or(b' ');
+        if let Some(cell) = offset.to_usize().and_then(|i| self.cells.get_mut(i)) {
+            *cell = code;
+        }
+    }
+
+    fn peek(&self, offset: &Pos) -> Word {
+        offset.to_usize().and_then(|i| self.cells.get(i)).map_or(0, |&c| c).into()
+    }
+
+    fn box_clone(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for CharDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.cells.len() / self.width {
+            writeln!(f, "{}", self.row(y).trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn mapped_devices() {
+    use crate::intcode_full::{State, YieldReason};
+
+    let program = "1101,72,0,2000,1101,105,0,2001,4,1000,4,1001,4,1001,1101,7,0,1001,4,1001,99";
+    let mut state = State::new_from_str(program, std::iter::empty());
+    state.attach(1000usize.into()..1001usize.into(), CycleCounter::default());
+    state.attach(1001usize.into()..1002usize.into(), Random::new(1, 6));
+    state.attach(2000usize.into()..2010usize.into(), CharDisplay::new(5, 2));
+
+    let peeked = state.read(&1001usize.into());
+    assert_eq!(state.read(&1001usize.into()), peeked);
+
+    let copy = state.clone();
+    let (outputs, reason) = state.run_to_yield();
+    assert_eq!(reason, YieldReason::Halt);
+    assert_eq!(copy.clone().run_to_yield().0, outputs);
+
+    assert_eq!(outputs[0], Word::from(2));
+    assert_eq!(outputs[1], peeked);
+    assert!(outputs[1..].iter().all(|w| w < &Word::from(6)));
+    assert_eq!(outputs[3], Word::from(SplitMix::new(7).below(6)));
+
+    assert_eq!(state.device::<CycleCounter>().unwrap().cycles, 8);
+    assert_eq!(state.device::<CharDisplay>().unwrap().to_string(), "Hi\n\n");
+    assert_eq!(state.read(&2001usize.into()), Word::from(105));
+    assert_eq!(state.read(&2000usize.into()), Word::from(72));
+    assert!(state.cells().all(|(pos, _)| pos < &1000usize.into()));
+}
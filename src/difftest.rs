@@ -0,0 +1,293 @@
+use std::ops::Range;
+
+use itertools::join;
+use boolinator::Boolinator;
+
+use crate::rng::SplitMix;
+use crate::{intcode, intcode_full};
+use crate::intcode_full::{InputWord, word_narrow};
+
+const MAX_INSNS: usize = 16;
+const REGISTERS: usize = 6;
+const SINKS: usize = 2;
+
+pub struct Spec {
+    pub opcodes: &'static [InputWord],
+    pub immediate: bool,
+    pub inputs: usize,
+    pub faults: bool,
+    pub code_writes: bool
+}
+
+pub const DAY_2: Spec = Spec { opcodes: &[1, 2], immediate: false, inputs: 0, faults: false, code_writes: false };
+pub const DAY_5: Spec = Spec {
+    opcodes: &[1, 2, 3, 4, 5, 6, 7, 8], immediate: true, inputs: 1, faults: false, code_writes: false
+};
+
+// Whole instruction words standing in for an opcode: an unknown opcode, an `Add` writing
+// through an immediate destination and a jump past the end of memory.
+const UNKNOWN: InputWord = 42;
+const IMMEDIATE_DEST: InputWord = 11101;
+const FAR_JUMP: InputWord = 1105;
+const FAULTS: [InputWord; 3] = [UNKNOWN, IMMEDIATE_DEST, FAR_JUMP];
+
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub program: Vec<InputWord>,
+    pub inputs: Vec<InputWord>
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Outcome {
+    pub outputs: Vec<InputWord>,
+    pub memory: Vec<InputWord>,
+    pub reason: Reason
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Reason {
+    Halt,
+    WaitInput,
+    Fault
+}
+
+impl From<intcode::YieldReason> for Reason {
+    fn from(reason: intcode::YieldReason) -> Self {
+        match reason {
+            intcode::YieldReason::Halt => Reason::Halt,
+            intcode::YieldReason::WaitInput => Reason::WaitInput,
+            intcode::YieldReason::IncorrectOpcode | intcode::YieldReason::NoSuchArg => Reason::Fault
+        }
+    }
+}
+
+impl From<intcode_full::YieldReason> for Reason {
+    fn from(reason: intcode_full::YieldReason) -> Self {
+        match reason {
+            intcode_full::YieldReason::Halt => Reason::Halt,
+            intcode_full::YieldReason::WaitInput => Reason::WaitInput,
+            _ => Reason::Fault
+        }
+    }
+}
+
+pub type Runner<'a> = (&'a str, &'a dyn Fn(&Case) -> Outcome);
+
+fn arity(opcode: InputWord) -> usize {
+    match opcode % 100 {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        3 | 4 => 1,
+        UNKNOWN => 0,
+        _ => unreachable!()
+    }
+}
+
+struct Layout {
+    starts: Vec<usize>,
+    halt: usize
+}
+
+impl Layout {
+    fn registers(&self) -> Range<usize> {
+        self.halt + 1..self.halt + 1 + REGISTERS
+    }
+
+    fn sinks(&self) -> Range<usize> {
+        self.registers().end..self.registers().end + SINKS
+    }
+
+    fn readable(&self) -> usize {
+        self.registers().end
+    }
+}
+
+// Arithmetic only reads code and registers while `Mul` writes into sinks that are never
+// read back, which keeps every value far below `isize` overflow and the day 2 `usize` VM sane.
+fn source(spec: &Spec, rng: &mut SplitMix, readable: usize) -> (InputWord, InputWord) {
+    if spec.immediate && rng.below(3) == 0 {
+        (1, rng.between(-9, 9))
+    } else {
+        (0, rng.below(readable) as InputWord)
+    }
+}
+
+fn pick(rng: &mut SplitMix, range: Range<usize>) -> InputWord {
+    (range.start + rng.below(range.len())) as InputWord
+}
+
+pub fn generate(spec: &Spec, seed: u64) -> Case {
+    let mut rng = SplitMix::new(seed);
+
+    let count = 1 + rng.below(MAX_INSNS);
+    let mut inputs_left = spec.inputs;
+    let mut opcodes = Vec::new();
+
+    while opcodes.len() < count {
+        let opcode = if spec.faults && rng.below(8) == 0 {
+            FAULTS[rng.below(FAULTS.len())]
+        } else {
+            spec.opcodes[rng.below(spec.opcodes.len())]
+        };
+
+        if opcode == 3 {
+            if inputs_left == 0 {
+                continue;
+            }
+
+            inputs_left -= 1;
+        }
+
+        opcodes.push(opcode);
+    }
+
+    let starts: Vec<usize> = opcodes.iter()
+        .scan(0, |pos, &opcode| {
+            let start = *pos;
+            *pos += 1 + arity(opcode);
+            Some(start)
+        })
+        .collect();
+
+    let halt = starts.last().map_or(0, |&s| s + 1 + arity(*opcodes.last().unwrap()));
+    let layout = Layout { starts, halt };
+
+    let mut program = Vec::new();
+
+    // Position-mode operands read by each instruction and comparisons that may redirect one of
+    // the later ones, so code-region writes only ever swap an address for 0 or 1.
+    let mut operands = Vec::new();
+    let mut code_writes = Vec::new();
+
+    for (i, &opcode) in opcodes.iter().enumerate() {
+        let start = layout.starts[i];
+
+        let (modes, args) = match opcode {
+            1 | 2 | 7 | 8 => {
+                let (m1, a1) = source(spec, &mut rng, layout.readable());
+                let (m2, a2) = source(spec, &mut rng, layout.readable());
+                let dest = match opcode {
+                    2 => pick(&mut rng, layout.sinks()),
+                    _ => pick(&mut rng, layout.registers())
+                };
+
+                operands.extend((m1 == 0).as_some((i, start + 1)));
+                operands.extend((m2 == 0).as_some((i, start + 2)));
+                code_writes.extend((spec.code_writes && opcode >= 7 && rng.below(2) == 0).as_some((i, start + 3)));
+
+                (m1 + 10 * m2, vec![a1, a2, dest])
+            },
+
+            3 => (0, vec![pick(&mut rng, layout.registers())]),
+
+            4 => {
+                let (m, a) = source(spec, &mut rng, layout.sinks().end);
+                operands.extend((m == 0).as_some((i, start + 1)));
+
+                (m, vec![a])
+            },
+
+            UNKNOWN => (0, vec![]),
+            IMMEDIATE_DEST => (0, (0..3).map(|_| rng.between(-9, 9)).collect()),
+            FAR_JUMP => (0, vec![1, pick(&mut rng, layout.sinks().end..layout.sinks().end + 10)]),
+
+            5 | 6 => {
+                let (m, cond) = source(spec, &mut rng, layout.readable());
+                operands.extend((m == 0).as_some((i, start + 1)));
+
+                let targets = layout.starts.len() - i;
+                let target = layout.starts.get(i + 1 + rng.below(targets)).cloned().unwrap_or(layout.halt);
+
+                (m + 10, vec![cond, target as InputWord])
+            },
+
+            _ => unreachable!()
+        };
+
+        program.push(opcode + 100 * modes);
+        program.extend(args);
+    }
+
+    program.push(99);
+    program.extend((0..REGISTERS + SINKS).map(|_| rng.between(0, 9)));
+
+    for (i, dest) in code_writes {
+        let later: Vec<_> = operands.iter().filter(|&&(j, _)| j > i).map(|&(_, cell)| cell).collect();
+        if !later.is_empty() {
+            program[dest] = later[rng.below(later.len())] as InputWord;
+        }
+    }
+
+    let inputs = (0..spec.inputs).map(|_| rng.between(-9, 9)).collect();
+
+    Case { program, inputs }
+}
+
+impl Case {
+    pub fn program_str(&self) -> String {
+        join(&self.program, ",")
+    }
+}
+
+pub fn run_intcode(case: &Case) -> Outcome {
+    let mut state = intcode::State::new_from_str(&case.program_str(), case.inputs.iter().cloned());
+    let (outputs, reason) = state.run_to_yield();
+
+    Outcome {
+        outputs,
+        memory: state.memory().to_vec(),
+        reason: reason.into()
+    }
+}
+
+pub fn run_intcode_full(case: &Case) -> Outcome {
+    let mut state = intcode_full::State::new_from_str(&case.program_str(), case.inputs.iter().cloned());
+    let (outputs, reason) = state.run_to_yield();
+
+    let narrow = |w| word_narrow(w).expect("Generated values are small!");
+
+    Outcome {
+        outputs: outputs.into_iter().map(narrow).collect(),
+        memory: state.read_range(&0usize.into(), case.program.len()).into_iter().map(narrow).collect(),
+        reason: reason.into()
+    }
+}
+
+pub fn check(spec: &Spec, seeds: Range<u64>, runners: &[Runner]) {
+    for seed in seeds {
+        let case = generate(spec, seed);
+        let outcomes: Vec<_> = runners.iter().map(|(name, run)| (name, run(&case))).collect();
+
+        let (reference_name, reference) = &outcomes[0];
+        for (name, outcome) in outcomes.iter().skip(1) {
+            assert!(outcome == reference,
+                    "Divergence on seed {}: {}\ninputs: {:?}\n{}: {:?}\n{}: {:?}",
+                    seed, case.program_str(), case.inputs, reference_name, reference, name, outcome);
+        }
+    }
+}
+
+#[test]
+fn generated_programs_halt() {
+    for seed in 0..100 {
+        assert_eq!(run_intcode_full(&generate(&DAY_5, seed)).reason, Reason::Halt);
+    }
+}
+
+#[test]
+fn generated_programs_fault() {
+    let spec = Spec { faults: true, code_writes: true, ..DAY_5 };
+    let outcomes: Vec<_> = (0..200).map(|seed| run_intcode_full(&generate(&spec, seed))).collect();
+
+    assert!(outcomes.iter().any(|outcome| outcome.reason == Reason::Fault));
+    assert!(outcomes.iter().any(|outcome| outcome.reason == Reason::Halt));
+}
+
+#[test]
+fn intcode_matches_intcode_full() {
+    let spec = Spec { inputs: 4, ..DAY_5 };
+    check(&spec, 0..500, &[("intcode", &run_intcode), ("intcode_full", &run_intcode_full)]);
+
+    let spec = Spec { inputs: 4, faults: true, code_writes: true, ..DAY_5 };
+    check(&spec, 0..2000, &[("intcode", &run_intcode), ("intcode_full", &run_intcode_full)]);
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use itertools::join;
+use num_traits::{Signed, cast::ToPrimitive};
+
+use crate::intcode_full::{State, Opcodes, Opcode, Executed, Pos, Word, MAX_PARAMS};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Line {
+    pub address: Pos,
+    pub words: Vec<Word>,
+    pub text: String
+}
+
+fn mnemonic(opcode: &Opcode, code: usize) -> String {
+    let name = match opcode {
+        Opcode::Add => "add",
+        Opcode::Mul => "mul",
+        Opcode::Halt => "hlt",
+        Opcode::Input => "in",
+        Opcode::Output => "out",
+        Opcode::JumpIfTrue => "jt",
+        Opcode::JumpIfFalse => "jf",
+        Opcode::LessThan => "lt",
+        Opcode::Equals => "eq",
+        Opcode::AdjRelBase => "arb",
+        Opcode::Extension(_) => return format!("ext{}", code)
+    };
+
+    name.to_string()
+}
+
+fn operand(mode: usize, arg: &Word) -> String {
+    match mode {
+        0 => format!("[{}]", arg),
+        1 => arg.to_string(),
+        2 if arg.is_negative() => format!("[rb{}]", arg),
+        2 => format!("[rb+{}]", arg),
+        _ => format!("?{}:{}", mode, arg)
+    }
+}
+
+// Returns the instruction length in words and its text; undecodable words are shown as `data`.
+pub fn decode(opcodes: &Opcodes, words: &[Word]) -> (usize, String) {
+    let data = || (1, format!("data {}", words[0]));
+
+    let code = match words[0].to_usize() {
+        Some(code) => code,
+        None => return data()
+    };
+
+    let (opcode, params) = match opcodes.lookup(code % 100) {
+        Some(found) if found.1 < words.len() => found,
+        _ => return data()
+    };
+
+    let modes = (0..params).scan(code / 100, |m, _| {
+        let mode = *m % 10;
+        *m /= 10;
+        Some(mode)
+    });
+
+    let args = modes.zip(words[1..].iter()).map(|(mode, arg)| operand(mode, arg));
+    let text = match params {
+        0 => mnemonic(&opcode, code % 100),
+        _ => format!("{} {}", mnemonic(&opcode, code % 100), join(args, ", "))
+    };
+
+    (1 + params, text)
+}
+
+impl Line {
+    pub fn at(state: &State, address: &Pos) -> Self {
+        let window: Vec<Word> = (0..=MAX_PARAMS).map(|i| state.read(&(address + i))).collect();
+        let (len, text) = decode(state.opcodes(), &window);
+
+        Line { address: address.clone(), words: window[..len].to_vec(), text }
+    }
+
+    pub fn executed(state: &State, executed: &Executed) -> Self {
+        let (_, text) = decode(state.opcodes(), &executed.words);
+        Line { address: executed.ip.clone(), words: executed.words.clone(), text }
+    }
+
+    pub fn end(&self) -> Pos {
+        &self.address + self.words.len()
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>8}  {:<28} {}", self.address, join(&self.words, ","), self.text)
+    }
+}
+
+pub fn disassemble(state: &State, start: &Pos, count: usize) -> Vec<Line> {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut address = start.clone();
+
+    while lines.len() < count {
+        let line = Line::at(state, &address);
+        address = line.end();
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[test]
+fn decodes_modes() {
+    let state = State::new_from_str("1001,20,1,21,204,-3,109,5,3,7,99,42", std::iter::empty());
+    let text: Vec<_> = disassemble(&state, &0usize.into(), 6).into_iter().map(|l| l.text).collect();
+
+    assert_eq!(text, ["add [20], 1, [21]", "out [rb-3]", "arb 5", "in [7]", "hlt", "data 42"]);
+}
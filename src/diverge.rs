@@ -0,0 +1,248 @@
+use std::fmt;
+
+use boolinator::Boolinator;
+use num_traits::cast::ToPrimitive;
+
+use crate::disasm::Line;
+use crate::intcode_full::{State, Opcode, YieldReason, Pos, Word};
+
+pub const CONTEXT: usize = 8;
+const COLUMN: usize = 56;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Ip,
+    Instruction,
+    Operand(usize),
+    Yield,
+    Write,
+    Output
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Side {
+    pub history: Vec<Line>,
+    pub current: Line,
+    pub detail: String
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Divergence {
+    pub step: usize,
+    pub kind: Kind,
+    pub left: Side,
+    pub right: Side
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    Diverged(Box<Divergence>),
+    Yielded { step: usize, reason: YieldReason },
+    Exhausted
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Operand {
+    Value(Word),
+    Address(Option<Pos>)
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Value(w) => write!(f, "{}", w),
+            Operand::Address(Some(pos)) => write!(f, "-> [{}]", pos),
+            Operand::Address(None) => write!(f, "-> negative address")
+        }
+    }
+}
+
+// Values the instruction at `ip` reads, with destination parameters resolved to their addresses.
+fn operands(state: &State, line: &Line) -> Vec<Operand> {
+    let code = match line.words[0].to_usize() {
+        Some(code) => code,
+        None => return Vec::new()
+    };
+
+    let destination = match state.opcodes().lookup(code % 100) {
+        Some((Opcode::Add, _)) | Some((Opcode::Mul, _)) | Some((Opcode::LessThan, _)) | Some((Opcode::Equals, _)) => Some(2),
+        Some((Opcode::Input, _)) => Some(0),
+        _ => None
+    };
+
+    let mut modes = code / 100;
+    line.words[1..].iter().enumerate().map(|(i, arg)| {
+        let mode = modes % 10;
+        modes /= 10;
+
+        let address = match mode {
+            1 => return Operand::Value(arg.clone()),
+            2 => (state.rel_base() + arg).to_biguint(),
+            _ => arg.to_biguint()
+        };
+
+        match (destination == Some(i), address) {
+            (true, address) => Operand::Address(address),
+            (false, Some(address)) => Operand::Value(state.read(&address)),
+            (false, None) => Operand::Address(None)
+        }
+    }).collect()
+}
+
+fn write_detail(state: &State) -> String {
+    match state.last_write() {
+        Some(pos) => format!("writes {} to [{}]", state.read(pos), pos),
+        None => "no write".to_string()
+    }
+}
+
+fn yield_detail(result: &Result<(), YieldReason>) -> String {
+    match result {
+        Ok(()) => "continues".to_string(),
+        Err(reason) => format!("yields {:?}", reason)
+    }
+}
+
+// After a step the diverging instruction is the newest history entry rather than part of the context.
+fn side(state: &State, current: &Line, executed: bool, detail: String) -> Side {
+    let mut history: Vec<Line> = state.history().map(|e| Line::executed(state, e)).collect();
+    if executed {
+        history.pop();
+    }
+
+    Side { history, current: current.clone(), detail }
+}
+
+// Steps both machines in lockstep, keeping only the last `CONTEXT` instructions of each, and stops
+// at the first step where they disagree, at the step both yield identically, or when `limit` runs out.
+pub fn first_divergence(mut left: State, mut right: State, limit: usize) -> Comparison {
+    left.keep_history(CONTEXT);
+    right.keep_history(CONTEXT);
+
+    for step in 0..limit {
+        let (l_line, r_line) = (Line::at(&left, left.ip()), Line::at(&right, right.ip()));
+        let found = |kind, left: Side, right: Side| Comparison::Diverged(Box::new(Divergence { step, kind, left, right }));
+
+        if left.ip() != right.ip() {
+            let detail = |s: &State| format!("ip {}", s.ip());
+            return found(Kind::Ip, side(&left, &l_line, false, detail(&left)), side(&right, &r_line, false, detail(&right)));
+        }
+
+        if let Some(i) = (0..l_line.words.len().max(r_line.words.len())).find(|&i| l_line.words.get(i) != r_line.words.get(i)) {
+            let detail = |line: &Line| format!("word {} = {}", i, line.words.get(i).map_or_else(|| "-".to_string(), Word::to_string));
+            return found(Kind::Instruction, side(&left, &l_line, false, detail(&l_line)), side(&right, &r_line, false, detail(&r_line)));
+        }
+
+        let (l_ops, r_ops) = (operands(&left, &l_line), operands(&right, &r_line));
+        if let Some(i) = (0..l_ops.len()).find(|&i| l_ops[i] != r_ops[i]) {
+            let detail = |ops: &[Operand]| format!("operand {} = {}", i + 1, ops[i]);
+            return found(Kind::Operand(i), side(&left, &l_line, false, detail(&l_ops)), side(&right, &r_line, false, detail(&r_ops)));
+        }
+
+        let (l_result, r_result) = (left.step(), right.step());
+        let executed = l_result.is_ok();
+
+        if l_result != r_result {
+            return found(Kind::Yield, side(&left, &l_line, l_result.is_ok(), yield_detail(&l_result)),
+                         side(&right, &r_line, r_result.is_ok(), yield_detail(&r_result)));
+        }
+
+        let write = |s: &State| s.last_write().map(|pos| (pos.clone(), s.read(pos)));
+        if write(&left) != write(&right) {
+            return found(Kind::Write, side(&left, &l_line, true, write_detail(&left)), side(&right, &r_line, true, write_detail(&right)));
+        }
+
+        let (l_out, r_out) = (left.take_output(), right.take_output());
+        if l_out != r_out {
+            let detail = |w: Option<Word>| w.map_or_else(|| "no output".to_string(), |w| format!("outputs {}", w));
+            return found(Kind::Output, side(&left, &l_line, executed, detail(l_out)), side(&right, &r_line, executed, detail(r_out)));
+        }
+
+        if let Err(reason) = l_result {
+            return Comparison::Yielded { step, reason };
+        }
+    }
+
+    Comparison::Exhausted
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Ip => write!(f, "instruction pointer"),
+            Kind::Instruction => write!(f, "instruction words"),
+            Kind::Operand(i) => write!(f, "operand {}", i + 1),
+            Kind::Yield => write!(f, "yield reason"),
+            Kind::Write => write!(f, "memory write"),
+            Kind::Output => write!(f, "output")
+        }
+    }
+}
+
+fn columns(f: &mut fmt::Formatter, left: &str, right: &str) -> fmt::Result {
+    writeln!(f, "{:<width$} | {}", left, right, width = COLUMN)
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "diverged at step {}: {}", self.step, self.kind)?;
+        columns(f, "left", "right")?;
+
+        let (l, r) = (&self.left.history, &self.right.history);
+        let rows = l.len().max(r.len());
+        let cell = |lines: &[Line], row: usize| {
+            let skip = rows - lines.len();
+            (row >= skip).as_some_from(|| format!("   {}", lines[row - skip])).unwrap_or_default()
+        };
+
+        for row in 0..rows {
+            columns(f, &cell(l, row), &cell(r, row))?;
+        }
+
+        columns(f, &format!("=> {}", self.left.current), &format!("=> {}", self.right.current))?;
+        columns(f, &format!("   {}", self.left.detail), &format!("   {}", self.right.detail))
+    }
+}
+
+#[cfg(test)]
+fn diverged(comparison: Comparison) -> Divergence {
+    match comparison {
+        Comparison::Diverged(divergence) => *divergence,
+        other => panic!("No divergence: {:?}", other)
+    }
+}
+
+#[test]
+fn finds_first_difference() {
+    let start = |s: &str, inputs: &[isize]| State::new_from_str(s, inputs.iter().cloned());
+
+    let echo = "3,20,1008,20,5,21,4,21,99";
+    let d = diverged(first_divergence(start(echo, &[5]), start(echo, &[6]), 100));
+    assert_eq!((d.step, d.kind), (0, Kind::Write));
+    assert_eq!((d.left.detail.as_str(), d.right.detail.as_str()), ("writes 5 to [20]", "writes 6 to [20]"));
+
+    let branch = "1005,10,6,104,0,99,104,1,99,0,0";
+    let mut patched = start(branch, &[]);
+    patched.write_memory(10, 1);
+    let d = diverged(first_divergence(start(branch, &[]), patched, 100));
+    assert_eq!((d.step, d.kind), (0, Kind::Operand(0)));
+    assert_eq!(d.right.detail, "operand 1 = 1");
+
+    let sums = "1101,2,3,20,1101,4,5,21,4,21,99";
+    let mut patched = start(sums, &[]);
+    patched.write_memory(5, 6);
+    let d = diverged(first_divergence(start(sums, &[]), patched, 100));
+    assert_eq!((d.step, d.kind), (1, Kind::Instruction));
+    assert_eq!((d.left.history.len(), d.left.detail.as_str(), d.right.detail.as_str()), (1, "word 1 = 4", "word 1 = 6"));
+    assert_eq!(d.to_string().lines().nth(3).unwrap().trim_end(), format!("{:<56} | => {}", format!("=> {}", d.left.current), d.right.current));
+}
+
+#[test]
+fn tells_identical_yields_from_exhausted_limit() {
+    let start = |s: &str, inputs: &[isize]| State::new_from_str(s, inputs.iter().cloned());
+
+    let echo = "3,20,1008,20,5,21,4,21,99";
+    assert_eq!(first_divergence(start(echo, &[5]), start(echo, &[5]), 100), Comparison::Yielded { step: 4, reason: YieldReason::Halt });
+    assert_eq!(first_divergence(start(echo, &[]), start(echo, &[]), 100), Comparison::Yielded { step: 0, reason: YieldReason::WaitInput });
+    assert_eq!(first_divergence(start(echo, &[5]), start(echo, &[5]), 3), Comparison::Exhausted);
+}
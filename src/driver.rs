@@ -0,0 +1,182 @@
+use std::rc::Rc;
+use std::pin::Pin;
+use std::cell::RefCell;
+use std::future::Future;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::intcode_full::{State, YieldReason, Word};
+
+struct Shared {
+    state: State,
+    reader: Option<Waker>
+}
+
+#[derive(Clone)]
+pub struct Machine(Rc<RefCell<Shared>>);
+
+pub struct Read(Machine);
+
+impl Machine {
+    pub fn new(state: State) -> Self {
+        Machine(Rc::new(RefCell::new(Shared { state, reader: None })))
+    }
+
+    pub fn read(&self) -> Read {
+        Read(self.clone())
+    }
+
+    pub async fn write(&self, input: impl Into<Word>) {
+        self.supply(input.into());
+    }
+
+    pub fn supply(&self, input: Word) {
+        let mut shared = self.0.borrow_mut();
+        shared.state.supply_word(input);
+
+        if let Some(reader) = shared.reader.take() {
+            reader.wake();
+        }
+    }
+
+    pub fn with_state<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        f(&mut self.0.borrow_mut().state)
+    }
+}
+
+impl Future for Read {
+    type Output = Result<Word, YieldReason>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut shared = (self.0).0.borrow_mut();
+
+        loop {
+            if let Some(output) = shared.state.take_output() {
+                return Poll::Ready(Ok(output));
+            }
+
+            match shared.state.step() {
+                Ok(()) => continue,
+                Err(YieldReason::WaitInput) => {
+                    shared.reader = Some(cx.waker().clone());
+                    return Poll::Pending;
+                },
+                Err(reason) => return Poll::Ready(Err(reason))
+            }
+        }
+    }
+}
+
+type Task<'a> = Pin<Box<dyn Future<Output=()> + 'a>>;
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().expect("Not poisoned.").push_back(self.id);
+    }
+}
+
+pub struct Handle<T>(Rc<RefCell<Option<T>>>);
+
+impl<T> Handle<T> {
+    pub fn take(&self) -> Option<T> {
+        self.0.borrow_mut().take()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Stalled {
+    pub pending: usize
+}
+
+#[derive(Default)]
+pub struct Executor<'a> {
+    tasks: Vec<Option<Task<'a>>>,
+    ready: Arc<Mutex<VecDeque<usize>>>
+}
+
+impl<'a> Executor<'a> {
+    pub fn new() -> Self {
+        Executor { tasks: Vec::new(), ready: Default::default() }
+    }
+
+    pub fn spawn<T: 'a>(&mut self, future: impl Future<Output=T> + 'a) -> Handle<T> {
+        let slot = Rc::new(RefCell::new(None));
+        let result = slot.clone();
+
+        self.ready.lock().expect("Not poisoned.").push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(async move {
+            let value = future.await;
+            result.borrow_mut().replace(value);
+        })));
+
+        Handle(slot)
+    }
+
+    pub fn run(&mut self) -> Result<(), Stalled> {
+        loop {
+            let next = self.ready.lock().expect("Not poisoned.").pop_front();
+
+            let id = match next {
+                Some(id) => id,
+                None => {
+                    let pending = self.tasks.iter().filter(|t| t.is_some()).count();
+                    return if pending == 0 { Ok(()) } else { Err(Stalled { pending }) };
+                }
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: self.ready.clone() }));
+            let mut cx = Context::from_waker(&waker);
+
+            if let Some(task) = self.tasks[id].as_mut() {
+                if task.as_mut().poll(&mut cx).is_ready() {
+                    self.tasks[id] = None;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn ping_pong() {
+    let double = "3,20,1002,20,2,20,4,20,1105,1,0";
+    let (a, b) = (Machine::new(State::new_from_str(double, [1isize].iter().cloned())),
+                  Machine::new(State::new_from_str(double, std::iter::empty())));
+
+    let mut executor = Executor::new();
+
+    let (a_, b_) = (a.clone(), b.clone());
+    let result = executor.spawn(async move {
+        let mut value = Word::from(0);
+        for _ in 0..5 {
+            value = a_.read().await.unwrap();
+            b_.write(value).await;
+            value = b_.read().await.unwrap();
+            a_.write(value.clone()).await;
+        }
+
+        value
+    });
+
+    assert_eq!(executor.run(), Ok(()));
+    assert_eq!(result.take(), Some(Word::from(1024)));
+}
+
+#[test]
+fn detects_stall() {
+    let machine = Machine::new(State::new_from_str("3,0,4,0,99", std::iter::empty()));
+    let mut executor = Executor::new();
+
+    let reader = machine.clone();
+    executor.spawn(async move { reader.read().await });
+
+    assert_eq!(executor.run(), Err(Stalled { pending: 1 }));
+
+    machine.supply(Word::from(7));
+    assert_eq!(executor.run(), Ok(()));
+}
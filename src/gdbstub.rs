@@ -0,0 +1,468 @@
+use std::io::{self, Read, Write, BufRead};
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use itertools::join;
+use num_traits::cast::ToPrimitive;
+
+use crate::intcode_full::{State, Semantics, YieldReason, Pos, Word};
+
+// Every Intcode word is exposed to gdb as 8 little-endian bytes of its i64 value, so byte
+// address `a` lives in word `a / 8`. Registers are `ip` and `rel_base` in the same encoding.
+const WORD_BYTES: usize = 8;
+const REGISTERS: usize = 2;
+// Replies are hex encoded, so a memory read is capped at half the advertised packet size.
+const PACKET_SIZE: usize = 0x4000;
+// A running `c` looks for a 0x03 interrupt from gdb every this many steps.
+const SLICE: usize = 10_000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.aoc2019.intcode">
+    <reg name="ip" bitsize="64" type="code_ptr" regnum="0"/>
+    <reg name="rel_base" bitsize="64" type="int64" regnum="1"/>
+  </feature>
+</target>
+"#;
+
+pub struct Session {
+    state: State,
+    breakpoints: BTreeSet<Pos>,
+    swbreak: bool,
+    detached: bool
+}
+
+enum Stop {
+    Step,
+    Breakpoint,
+    Interrupt,
+    Yield(YieldReason)
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b))
+}
+
+pub fn frame(data: &str) -> String {
+    format!("${}#{:02x}", data, checksum(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn word_bytes(w: &Word) -> [u8; WORD_BYTES] {
+    let wrapped = Semantics::WrapI64.apply(w.clone()).expect("Wrapping never fails.");
+    wrapped.to_i64().expect("Wrapped to i64.").to_le_bytes()
+}
+
+fn bytes_word(bytes: &[u8]) -> Option<Word> {
+    let mut buf = [0u8; WORD_BYTES];
+    buf.copy_from_slice(bytes.get(..WORD_BYTES)?);
+    Some(i64::from_le_bytes(buf).into())
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+fn stop_reply(stop: Stop, swbreak: bool) -> String {
+    let signal = match stop {
+        Stop::Breakpoint if swbreak => return "T05swbreak:;".to_string(),
+        Stop::Step | Stop::Breakpoint | Stop::Yield(YieldReason::WaitInput) | Stop::Yield(YieldReason::Trap(_)) => 5,
+        Stop::Interrupt => 2,
+        Stop::Yield(YieldReason::Halt) => return "W00".to_string(),
+        Stop::Yield(YieldReason::IncorrectOpcode) | Stop::Yield(YieldReason::NoSuchArg) => 4,
+        Stop::Yield(YieldReason::Overflow) => 8,
+        Stop::Yield(_) => 11
+    };
+
+    format!("S{:02x}", signal)
+}
+
+fn console(text: &str) -> String {
+    format!("O{}", hex(text.as_bytes()))
+}
+
+impl Session {
+    pub fn new(state: State) -> Self {
+        Session { state, breakpoints: BTreeSet::new(), swbreak: false, detached: false }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    fn registers(&self) -> [Word; REGISTERS] {
+        [self.state.ip().clone().into(), self.state.rel_base().clone()]
+    }
+
+    fn set_register(&mut self, n: usize, value: Word) -> Option<()> {
+        match n {
+            0 => self.state.set_ip(value.to_biguint()?),
+            1 => self.state.set_rel_base(value),
+            _ => return None
+        }
+
+        Some(())
+    }
+
+    fn read_memory(&self, addr: usize, len: usize) -> Option<String> {
+        if len > PACKET_SIZE / 2 {
+            return None;
+        }
+
+        let bytes: Vec<u8> = (addr..addr.checked_add(len)?)
+            .map(|a| word_bytes(&self.state.read(&(a / WORD_BYTES).into()))[a % WORD_BYTES])
+            .collect();
+
+        Some(hex(&bytes))
+    }
+
+    fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Option<()> {
+        addr.checked_add(bytes.len())?;
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            let pos = Pos::from((addr + i) / WORD_BYTES);
+            let mut word = word_bytes(&self.state.read(&pos));
+            word[(addr + i) % WORD_BYTES] = byte;
+            self.state.write(pos, bytes_word(&word).expect("Full word."));
+        }
+
+        Some(())
+    }
+
+    // Outputs produced while running are forwarded to the gdb console before the stop reply.
+    fn run(&mut self, single: bool, interrupted: &mut dyn FnMut() -> bool) -> Vec<String> {
+        let mut steps = 0usize;
+
+        let stop = loop {
+            if steps > 0 && self.breakpoints.contains(self.state.ip()) {
+                break Stop::Breakpoint;
+            }
+
+            if steps > 0 && steps.is_multiple_of(SLICE) && interrupted() {
+                break Stop::Interrupt;
+            }
+
+            steps += 1;
+
+            if let Err(reason) = self.state.step() {
+                break Stop::Yield(reason);
+            }
+
+            if single {
+                break Stop::Step;
+            }
+        };
+
+        let mut replies: Vec<String> = Vec::new();
+        while let Some(output) = self.state.take_output() {
+            replies.push(console(&format!("output {}\n", output)));
+        }
+
+        if let Stop::Yield(YieldReason::WaitInput) = stop {
+            replies.push(console("waiting for input, use `monitor input <words>`\n"));
+        }
+
+        replies.push(stop_reply(stop, self.swbreak));
+        replies
+    }
+
+    fn monitor(&mut self, command: &str) -> Vec<String> {
+        let mut parts = command.trim().splitn(2, ' ');
+
+        match (parts.next(), parts.next()) {
+            (Some("input"), Some(words)) => {
+                let words: Option<Vec<Word>> = words.split(',').map(|w| w.trim().parse().ok()).collect();
+                match words {
+                    Some(words) => {
+                        words.into_iter().for_each(|w| self.state.supply_word(w));
+                        vec!["OK".to_string()]
+                    },
+                    None => vec!["E01".to_string()]
+                }
+            },
+
+            (Some("status"), None) => {
+                let status = format!("ip {} rel_base {} halted {} inputs [{}]\n",
+                                     self.state.ip(), self.state.rel_base(), self.state.is_halted(),
+                                     join(self.state.pending_inputs(), ","));
+                vec![console(&status), "OK".to_string()]
+            },
+
+            _ => vec![console("commands: input <w1,w2,...>, status\n"), "OK".to_string()]
+        }
+    }
+
+    fn breakpoint(&mut self, args: &str, insert: bool) -> Option<String> {
+        let mut parts = args.split(',');
+        if parts.next()? != "0" {
+            return Some(String::new());
+        }
+
+        let pos = Pos::from(parse_hex(parts.next()?)? / WORD_BYTES);
+        if insert {
+            self.breakpoints.insert(pos);
+        } else {
+            self.breakpoints.remove(&pos);
+        }
+
+        Some("OK".to_string())
+    }
+
+    fn xfer(&self, args: &str) -> Option<String> {
+        let range = args.strip_prefix("features:read:target.xml:")?;
+        let mut parts = range.split(',');
+        let (offset, len) = (parse_hex(parts.next()?)?, parse_hex(parts.next()?)?);
+
+        let end = offset.saturating_add(len);
+        let chunk = TARGET_XML.get(offset.min(TARGET_XML.len())..end.min(TARGET_XML.len()))?;
+        let more = if end < TARGET_XML.len() { "m" } else { "l" };
+        Some(format!("{}{}", more, chunk))
+    }
+
+    // `interrupted` is polled while continuing and reports whether gdb sent a 0x03 break.
+    pub fn handle(&mut self, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Vec<String> {
+        let reply = |s: Option<String>| vec![s.unwrap_or_else(|| "E01".to_string())];
+
+        match packet.chars().next() {
+            Some('?') => vec![stop_reply(Stop::Step, false)],
+            Some('g') => vec![self.registers().iter().map(|r| hex(&word_bytes(r))).collect()],
+            Some('G') => reply(unhex(&packet[1..]).and_then(|bytes| {
+                for (n, chunk) in bytes.chunks(WORD_BYTES).take(REGISTERS).enumerate() {
+                    self.set_register(n, bytes_word(chunk)?)?;
+                }
+
+                Some("OK".to_string())
+            })),
+            Some('p') => reply(parse_hex(&packet[1..])
+                .and_then(|n| self.registers().get(n).cloned())
+                .map(|r| hex(&word_bytes(&r)))),
+            Some('P') => reply((|| {
+                let mut parts = packet[1..].splitn(2, '=');
+                let n = parse_hex(parts.next()?)?;
+                self.set_register(n, bytes_word(&unhex(parts.next()?)?)?)?;
+                Some("OK".to_string())
+            })()),
+            Some('m') => reply((|| {
+                let mut parts = packet[1..].split(',');
+                self.read_memory(parse_hex(parts.next()?)?, parse_hex(parts.next()?)?)
+            })()),
+            Some('M') => reply((|| {
+                let mut parts = packet[1..].splitn(2, ':');
+                let addr = parse_hex(parts.next()?.split(',').next()?)?;
+                self.write_memory(addr, &unhex(parts.next()?)?)?;
+                Some("OK".to_string())
+            })()),
+            Some('s') => self.run(true, interrupted),
+            Some('c') => self.run(false, interrupted),
+            Some('Z') => reply(self.breakpoint(&packet[1..], true)),
+            Some('z') => reply(self.breakpoint(&packet[1..], false)),
+            Some('H') | Some('T') => vec!["OK".to_string()],
+            Some('D') => {
+                self.detached = true;
+                vec!["OK".to_string()]
+            },
+            Some('k') => {
+                self.detached = true;
+                Vec::new()
+            },
+            _ => match packet {
+                "qAttached" => vec!["1".to_string()],
+                "qC" => vec!["QC1".to_string()],
+                "qfThreadInfo" => vec!["m1".to_string()],
+                "qsThreadInfo" => vec!["l".to_string()],
+                _ if packet.starts_with("qSupported") => {
+                    self.swbreak = packet.split([':', ';']).any(|f| f == "swbreak+");
+                    vec![format!("PacketSize={:x};qXfer:features:read+;swbreak+", PACKET_SIZE)]
+                },
+                _ if packet.starts_with("qXfer:") => reply(self.xfer(&packet[6..])),
+                _ if packet.starts_with("qRcmd,") => match unhex(&packet[6..]).and_then(|c| String::from_utf8(c).ok()) {
+                    Some(command) => self.monitor(&command),
+                    None => vec!["E01".to_string()]
+                },
+                _ => vec![String::new()]
+            }
+        }
+    }
+}
+
+// Bytes from gdb are read on their own thread, so a running `c` can notice a 0x03 interrupt
+// without blocking on the connection.
+struct Incoming {
+    chunks: Receiver<Vec<u8>>,
+    pending: VecDeque<u8>
+}
+
+impl Incoming {
+    fn spawn<R: Read + Send + 'static>(mut reader: R) -> Self {
+        let (sender, chunks) = channel();
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 || sender.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Incoming { chunks, pending: VecDeque::new() }
+    }
+
+    fn interrupted(&mut self) -> bool {
+        while let Ok(chunk) = self.chunks.try_recv() {
+            self.pending.extend(chunk);
+        }
+
+        match self.pending.iter().position(|&b| b == 0x03) {
+            Some(i) => {
+                self.pending.remove(i);
+                true
+            },
+            None => false
+        }
+    }
+}
+
+impl Read for Incoming {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.fill_buf()?.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *dst = src;
+        }
+
+        Ok(n)
+    }
+}
+
+impl BufRead for Incoming {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pending.is_empty() {
+            if let Ok(chunk) = self.chunks.recv() {
+                self.pending.extend(chunk);
+            }
+        }
+
+        Ok(self.pending.make_contiguous())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pending.drain(..amt);
+    }
+}
+
+fn read_packet<R: BufRead>(reader: &mut R) -> io::Result<Option<(String, bool)>> {
+    let mut byte = [0u8];
+
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+
+        if byte[0] == b'$' {
+            break;
+        }
+    }
+
+    let mut data = Vec::new();
+    reader.read_until(b'#', &mut data)?;
+    data.pop();
+
+    let mut sum = [0u8; 2];
+    reader.read_exact(&mut sum)?;
+
+    let data = String::from_utf8_lossy(&data).into_owned();
+    let valid = std::str::from_utf8(&sum).ok().and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(checksum(&data));
+    Ok(Some((data, valid)))
+}
+
+pub fn serve<R: Read + Send + 'static, W: Write>(session: &mut Session, reader: R, mut writer: W) -> io::Result<()> {
+    let mut reader = Incoming::spawn(reader);
+
+    while !session.is_detached() {
+        let (packet, valid) = match read_packet(&mut reader)? {
+            Some(packet) => packet,
+            None => break
+        };
+
+        if !valid {
+            writer.write_all(b"-")?;
+            writer.flush()?;
+            continue;
+        }
+
+        writer.write_all(b"+")?;
+        for reply in session.handle(&packet, &mut || reader.interrupted()) {
+            writer.write_all(frame(&reply).as_bytes())?;
+        }
+
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn scripted_session() {
+    let program = "3,20,1001,20,1,20,4,20,99";
+    let mut session = Session::new(State::new_from_str(program, std::iter::empty()));
+
+    let monitor = format!("qRcmd,{}", hex(b"input 41"));
+    let script = [
+        "qSupported:swbreak+", "?", "g", "m0,10", "Z0,30,1", "c", &monitor, "c",
+        "ma0,8", "s", "p0", "M0,8:6300000000000000", "P0=0000000000000000", "c", "D"
+    ];
+
+    let input: String = script.iter().map(|p| frame(p)).collect();
+    let mut output = Vec::new();
+    serve(&mut session, io::Cursor::new(input.into_bytes()), &mut output).unwrap();
+
+    let expected = [
+        frame("PacketSize=4000;qXfer:features:read+;swbreak+"),
+        frame("S05"),
+        frame(&"0".repeat(32)),
+        frame("03000000000000001400000000000000"),
+        frame("OK"),
+        frame(&console("waiting for input, use `monitor input <words>`\n")) + &frame("S05"),
+        frame("OK"),
+        frame("T05swbreak:;"),
+        frame("2a00000000000000"),
+        frame(&console("output 42\n")) + &frame("S05"),
+        frame("0800000000000000"),
+        frame("OK"),
+        frame("OK"),
+        frame("W00"),
+        frame("OK")
+    ];
+
+    let acked: String = expected.iter().map(|r| format!("+{}", r)).collect();
+    assert_eq!(String::from_utf8(output).unwrap(), acked);
+}
+
+#[test]
+fn rejects_bad_ranges_and_interrupts() {
+    let mut session = Session::new(State::new_from_str("1105,1,0", std::iter::empty()));
+    let mut never = || false;
+
+    assert_eq!(session.handle("mffffffffffffffff,10", &mut never), ["E01"]);
+    assert_eq!(session.handle("m0,100000", &mut never), ["E01"]);
+    assert_eq!(session.handle("Mfffffffffffffffe,4:00000000", &mut never), ["E01"]);
+    assert_eq!(session.handle("qXfer:features:read:target.xml:10,ffffffffffffffff", &mut never)[0].chars().next(), Some('l'));
+
+    let input = format!("{}\x03{}", frame("c"), frame("D"));
+    let mut output = Vec::new();
+    serve(&mut session, io::Cursor::new(input.into_bytes()), &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), format!("+{}+{}", frame("S02"), frame("OK")));
+}
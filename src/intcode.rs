@@ -2,6 +2,7 @@ use std::convert::TryInto;
 
 use smallvec::SmallVec;
 use boolinator::Boolinator;
+use num_integer::Integer;
 
 const MAX_PARAMS: usize = 3;
 const INPUTS: usize = 2;
@@ -18,10 +19,6 @@ pub struct State {
     outputs: SmallVec<[Word; OUTPUTS]>
 }
 
-fn divrem(i: Word, m: Word) -> (Word, Word) {
-    (i % m, i / m)
-}
-
 #[derive(Debug)]
 enum Opcode {
     Add,
@@ -85,13 +82,13 @@ impl Insn {
     fn parse(memory: &[Word]) -> Option<Insn> {
         let (insn, args) = memory.split_first()?;
 
-        let (opcode, insn) = divrem(*insn, 100);
+        let (insn, opcode) = insn.div_rem(&100);
         let (opcode, arg_count) = Opcode::parse(opcode)?;
 
         let args: Option<_> = args.get(0..arg_count)?
             .iter()
             .scan(insn, |insn, &arg| {
-                let (im, insn_) = divrem(*insn, 10);
+                let (insn_, im) = insn.div_rem(&10);
                 *insn = insn_;
 
                 Some(Arg::parse(im, arg))
@@ -147,6 +144,10 @@ impl State {
         }
     }
 
+    pub fn memory(&self) -> &[Word] {
+        &self.memory
+    }
+
     pub fn supply_input(&mut self, input: Word) {
         self.inputs.insert(0, input)
     }
@@ -154,7 +155,7 @@ impl State {
     pub fn step(&mut self) -> Result<(), YieldReason> {
         (!self.halted).ok_or(YieldReason::Halt)?;
 
-        let insn = Insn::parse(&self.memory[self.pos..]).ok_or(YieldReason::IncorrectOpcode)?;
+        let insn = self.memory.get(self.pos..).and_then(Insn::parse).ok_or(YieldReason::IncorrectOpcode)?;
 
         let npos = match insn.opcode {
             Opcode::Halt => {
@@ -1,14 +1,24 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::any::Any;
+use std::sync::Arc;
+use std::ops::Range;
+use std::io::{self, Read, BufReader};
+#[cfg(test)]
+use std::iter::empty;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 use smallvec::SmallVec;
 use boolinator::Boolinator;
 use lazy_static::lazy_static;
 
+use crate::callstack::{CallStack, Frame};
+
+use num_traits::Zero;
 use num_traits::cast::ToPrimitive;
 use num_integer::Integer;
 use num_bigint::{BigUint, BigInt, ToBigInt, ToBigUint};
 
-const MAX_PARAMS: usize = 3;
+pub const MAX_PARAMS: usize = 3;
 const INPUTS: usize = 2;
 const OUTPUTS: usize = 4;
 
@@ -16,6 +26,7 @@ pub type Pos = BigUint;
 pub type Word = BigInt;
 pub type InputWord = isize;
 
+#[derive(Clone)]
 pub struct Memory(HashMap<Pos, Word>);
 
 pub fn word_narrow(w: Word) -> Option<InputWord> {
@@ -30,19 +41,77 @@ impl Memory {
     fn get_mut(&mut self, index: Pos) -> &mut Word {
         self.0.entry(index).or_insert_with(|| 0isize.into())
     }
+
+    fn cells(&self) -> impl Iterator<Item=(&Pos, &Word)> {
+        self.0.iter()
+    }
 }
 
+#[derive(Clone)]
 pub struct State {
     ip: Pos,
     rel_base: Word,
     halted: bool,
     memory: Memory,
+    opcodes: Arc<Opcodes>,
     inputs: SmallVec<[Word; INPUTS]>,
-    outputs: SmallVec<[Word; OUTPUTS]>
+    outputs: SmallVec<[Word; OUTPUTS]>,
+    history: VecDeque<Executed>,
+    history_len: usize,
+    last_write: Option<Pos>,
+    semantics: Semantics,
+    limits: Limits,
+    protected: Vec<Range<Pos>>,
+    write_fault: Option<WriteFault>,
+    calls: Option<CallStack>,
+    devices: Vec<(Range<Pos>, Box<dyn Device>)>
 }
 
-#[derive(Debug)]
-enum Opcode {
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WriteFault {
+    pub ip: Pos,
+    pub target: Pos
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Executed {
+    pub ip: Pos,
+    pub rel_base: Word,
+    pub words: Vec<Word>
+}
+
+// Handlers should write through `State::checked_write` so protection, limits and semantics apply.
+pub type Handler = Arc<dyn Fn(&mut State, &[Operand]) -> Result<Option<Pos>, YieldReason> + Send + Sync>;
+
+// Host-implemented hardware mapped over an address range. Program reads and writes in the range
+// go to the device with offsets relative to the range start; instruction fetch still uses memory.
+pub trait Device: Send + Sync {
+    fn read(&mut self, offset: &Pos) -> Word;
+    fn write(&mut self, offset: &Pos, value: Word);
+
+    // Side-effect free view for hosts, disassemblers and diffs.
+    fn peek(&self, offset: &Pos) -> Word;
+
+    fn tick(&mut self) {}
+
+    fn box_clone(&self) -> Box<dyn Device>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl Clone for Box<dyn Device> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Operand {
+    pub value: Word,
+    pub address: Option<Pos>
+}
+
+#[derive(Clone)]
+pub(crate) enum Opcode {
     Add,
     Mul,
     Halt,
@@ -52,36 +121,151 @@ enum Opcode {
     JumpIfFalse,
     LessThan,
     Equals,
-    AdjRelBase
+    AdjRelBase,
+    Extension(Handler)
 }
 
+#[derive(Clone)]
+pub struct Opcodes(HashMap<usize, (Opcode, usize)>);
+
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum YieldReason {
     IncorrectOpcode,
     NegativeAddress,
     NoSuchArg,
     WaitInput,
-    Halt
+    Halt,
+    Trap(usize),
+    Overflow,
+    LimitExceeded(Limit),
+    WriteProtected
 }
 
-impl Opcode {
-    fn parse(opcode: usize) -> Option<(Self, usize)> {
-        match opcode {
-            99 => Some((Opcode::Halt, 0)),
-            1 => Some((Opcode::Add, 3)),
-            2 => Some((Opcode::Mul, 3)),
-            3 => Some((Opcode::Input, 1)),
-            4 => Some((Opcode::Output, 1)),
-            5 => Some((Opcode::JumpIfTrue, 2)),
-            6 => Some((Opcode::JumpIfFalse, 2)),
-            7 => Some((Opcode::LessThan, 3)),
-            8 => Some((Opcode::Equals, 3)),
-            9 => Some((Opcode::AdjRelBase, 1)),
-            _ => None
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Limit {
+    Cells,
+    Address,
+    WordBits,
+    Inputs,
+    Outputs
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Limits {
+    pub cells: Option<usize>,
+    pub max_address: Option<Pos>,
+    pub word_bits: Option<usize>,
+    pub inputs: Option<usize>,
+    pub outputs: Option<usize>
+}
+
+impl Limits {
+    fn check_word(&self, w: &Word) -> Result<(), YieldReason> {
+        match self.word_bits {
+            Some(bits) if w.bits() > bits => Err(YieldReason::LimitExceeded(Limit::WordBits)),
+            _ => Ok(())
+        }
+    }
+
+    fn check_queue(limit: Option<usize>, len: usize, which: Limit) -> Result<(), YieldReason> {
+        match limit {
+            Some(limit) if len >= limit => Err(YieldReason::LimitExceeded(which)),
+            _ => Ok(())
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Semantics {
+    #[default]
+    Unbounded,
+    TrapI64,
+    WrapI64,
+    WrapI32
+}
+
+impl Semantics {
+    pub fn apply(self, w: Word) -> Result<Word, YieldReason> {
+        let wrap = |bits: usize| {
+            let modulus = Word::from(1) << bits;
+            let w = w.mod_floor(&modulus);
+            if w >= &modulus >> 1 { w - modulus } else { w }
+        };
+
+        match self {
+            Semantics::Unbounded => Ok(w),
+            Semantics::TrapI64 => w.to_i64().map(Word::from).ok_or(YieldReason::Overflow),
+            Semantics::WrapI64 => Ok(wrap(64)),
+            Semantics::WrapI32 => Ok(wrap(32))
         }
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Until {
+    Outputs(usize),
+    InputRequested,
+    Reached(Pos),
+    WriteTo(Pos)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    Outputs,
+    InputRequested,
+    Reached(Pos),
+    Write(Pos),
+    Yield(YieldReason)
+}
+
+impl YieldReason {
+    // Traps come from extension handlers such as asserts, so they count as faults too.
+    pub fn is_fault(self) -> bool {
+        matches!(self, YieldReason::IncorrectOpcode | YieldReason::NegativeAddress | YieldReason::NoSuchArg |
+                 YieldReason::Overflow | YieldReason::LimitExceeded(_) | YieldReason::WriteProtected |
+                 YieldReason::Trap(_))
+    }
+}
+
+impl Opcode {
+    pub(crate) fn parse(opcode: usize) -> Option<(Self, usize)> {
+        BUILTIN_OPCODES.lookup(opcode)
+    }
+}
+
+impl Default for Opcodes {
+    fn default() -> Self {
+        let builtins = vec![
+            (99, Opcode::Halt, 0),
+            (1, Opcode::Add, 3),
+            (2, Opcode::Mul, 3),
+            (3, Opcode::Input, 1),
+            (4, Opcode::Output, 1),
+            (5, Opcode::JumpIfTrue, 2),
+            (6, Opcode::JumpIfFalse, 2),
+            (7, Opcode::LessThan, 3),
+            (8, Opcode::Equals, 3),
+            (9, Opcode::AdjRelBase, 1)
+        ];
+
+        Opcodes(builtins.into_iter().map(|(code, opcode, params)| (code, (opcode, params))).collect())
+    }
+}
+
+impl Opcodes {
+    pub(crate) fn lookup(&self, opcode: usize) -> Option<(Opcode, usize)> {
+        self.0.get(&opcode).cloned()
+    }
+
+    pub fn register<F>(&mut self, code: usize, params: usize, handler: F)
+        where F: Fn(&mut State, &[Operand]) -> Result<Option<Pos>, YieldReason> + Send + Sync + 'static
+    {
+        assert!(code < 100, "Opcodes are the two lowest decimal digits!");
+        assert!(params <= MAX_PARAMS, "Too many parameters!");
+        self.0.insert(code, (Opcode::Extension(Arc::new(handler)), params));
+    }
+}
+
 #[derive(Clone)]
 enum Arg {
     Immediate(Word),
@@ -106,16 +290,17 @@ struct Insn {
 }
 
 lazy_static! {
+    static ref BUILTIN_OPCODES: Opcodes = Opcodes::default();
     static ref BUI_100: BigUint = 100usize.to_biguint().unwrap();
     static ref BUI_10: BigUint = 10usize.to_biguint().unwrap();
 }
 
 impl Insn {
-    fn parse(memory: &Memory, pos: &Pos) -> Option<Insn> {
+    fn parse(opcodes: &Opcodes, memory: &Memory, pos: &Pos) -> Option<Insn> {
         let insn = memory.get(pos).to_biguint()?;
 
         let (mut insn, opcode) = insn.div_rem(&*BUI_100);
-        let (opcode, arg_count) = Opcode::parse(opcode.to_usize()?)?;
+        let (opcode, arg_count) = opcodes.lookup(opcode.to_usize()?)?;
 
         let mut args: SmallVec<_> = SmallVec::new();
         for i in 1..=arg_count {
@@ -134,20 +319,107 @@ impl Insn {
     }
 }
 
-fn parse_program(s: &str) -> Option<Memory> {
-    let str_slices = s.trim().split(",").enumerate();
-    let bigints = str_slices.map(|(i, s)| {
-        let smallint: isize = s.parse().ok()?;
-        Some((i.to_biguint()?, smallint.to_bigint()?))
-    });
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Cell { index: usize, line: usize, text: String }
+}
 
-    let hashmap: Option<_> = bigints.collect();
-    Some(Memory(hashmap?))
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Cell { index, line, text } => write!(f, "line {}: cell {} is not an integer: {:?}", line, index, text)
+        }
+    }
+}
+
+fn finish(cell: &mut String, words: &mut Vec<Word>, line: usize) -> Result<(), LoadError> {
+    let word = cell.parse().map_err(|_| LoadError::Cell { index: words.len(), line, text: cell.clone() })?;
+    words.push(word);
+    cell.clear();
+    Ok(())
+}
+
+fn label(comment: &str) -> Option<&str> {
+    let name = comment.trim().strip_suffix(':')?;
+    (!name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')).as_some(name)
+}
+
+// Cells are separated by commas with any whitespace around them; `#` starts a comment that runs
+// to the end of the line. A single trailing comma is accepted.
+pub fn load_words<R: Read>(reader: R) -> Result<Vec<Word>, LoadError> {
+    load_labelled(reader).map(|(words, _)| words)
+}
+
+// A comment consisting of just `name:` labels the next cell.
+pub fn load_labelled<R: Read>(reader: R) -> Result<(Vec<Word>, BTreeMap<String, usize>), LoadError> {
+    let mut words = Vec::new();
+    let mut labels = BTreeMap::new();
+    let mut cell = String::new();
+    let mut text = String::new();
+    let mut gap = false;
+    let mut comment = false;
+    let mut line = 1;
+    let mut cell_line = 1;
+
+    for byte in BufReader::new(reader).bytes().chain(Some(Ok(b'\n'))) {
+        match byte? {
+            b'\n' => {
+                if let Some(name) = label(&text) {
+                    labels.insert(name.to_string(), words.len() + (!cell.is_empty()) as usize);
+                }
+
+                text.clear();
+                comment = false;
+                gap = !cell.is_empty();
+                line += 1;
+            },
+            b if comment => text.push(b as char),
+            b'#' => comment = true,
+            b',' => {
+                finish(&mut cell, &mut words, cell_line)?;
+                gap = false;
+            },
+            b if b.is_ascii_whitespace() => gap = !cell.is_empty(),
+            b if gap => {
+                cell.push(' ');
+                cell.push(b as char);
+            },
+            b => {
+                if cell.is_empty() {
+                    cell_line = line;
+                }
+
+                cell.push(b as char)
+            }
+        }
+    }
+
+    if !cell.is_empty() {
+        finish(&mut cell, &mut words, cell_line)?;
+    }
+
+    Ok((words, labels))
 }
 
 impl State {
     pub fn new_from_str(s: &str, inputs: impl Iterator<Item=InputWord>) -> Self {
-        let memory = parse_program(s).expect("Examples are correct.");
+        Self::load(s.as_bytes(), inputs).expect("Examples are correct.")
+    }
+
+    pub fn load<R: Read>(reader: R, inputs: impl Iterator<Item=InputWord>) -> Result<Self, LoadError> {
+        Ok(Self::from_words(load_words(reader)?, inputs))
+    }
+
+    pub fn from_words(words: Vec<Word>, inputs: impl Iterator<Item=InputWord>) -> Self {
+        let memory = Memory(words.into_iter().enumerate().map(|(i, w)| (i.into(), w)).collect());
 
         let mut inputs: SmallVec<_> = inputs.map(|iw| iw.into()).collect();
         inputs.reverse();
@@ -156,16 +428,145 @@ impl State {
             ip: 0usize.to_biguint().unwrap(),
             rel_base: 0isize.to_bigint().unwrap(),
             halted: false,
+            opcodes: Arc::new(Opcodes::default()),
             outputs: SmallVec::new(),
+            history: VecDeque::new(),
+            history_len: 0,
+            semantics: Semantics::default(),
+            limits: Limits::default(),
+            protected: Vec::new(),
+            write_fault: None,
+            calls: None,
+            devices: Vec::new(),
+            last_write: None,
             inputs,
             memory
         }
     }
 
+    pub fn set_opcodes(&mut self, opcodes: Opcodes) {
+        self.opcodes = Arc::new(opcodes);
+    }
+
+    pub fn register_opcode<F>(&mut self, code: usize, params: usize, handler: F)
+        where F: Fn(&mut State, &[Operand]) -> Result<Option<Pos>, YieldReason> + Send + Sync + 'static
+    {
+        Arc::make_mut(&mut self.opcodes).register(code, params, handler)
+    }
+
     pub fn is_halted(&self) -> bool {
         self.halted
     }
 
+    pub fn last_write(&self) -> Option<&Pos> {
+        self.last_write.as_ref()
+    }
+
+    pub fn ip(&self) -> &Pos {
+        &self.ip
+    }
+
+    pub fn rel_base(&self) -> &Word {
+        &self.rel_base
+    }
+
+    pub fn pending_inputs(&self) -> Vec<Word> {
+        self.inputs.iter().rev().cloned().collect()
+    }
+
+    pub fn pending_outputs(&self) -> &[Word] {
+        &self.outputs
+    }
+
+    pub fn track_calls(&mut self) {
+        self.calls.get_or_insert_with(CallStack::default);
+    }
+
+    pub fn backtrace(&self) -> Vec<Frame> {
+        self.calls.iter().flat_map(|calls| calls.frames().cloned()).collect()
+    }
+
+    // Earlier attachments win where ranges overlap.
+    pub fn attach(&mut self, range: Range<Pos>, device: impl Device + 'static) {
+        self.devices.push((range, Box::new(device)));
+    }
+
+    pub fn device<D: Device + 'static>(&self) -> Option<&D> {
+        self.devices.iter().find_map(|(_, device)| device.as_any().downcast_ref())
+    }
+
+    fn device_at(&mut self, pos: &Pos) -> Option<(Pos, &mut Box<dyn Device>)> {
+        let (range, device) = self.devices.iter_mut().find(|(range, _)| range.contains(pos))?;
+        Some((pos - &range.start, device))
+    }
+
+    fn fetch(&mut self, pos: &Pos) -> Word {
+        match self.device_at(pos) {
+            Some((offset, device)) => device.read(&offset),
+            None => self.memory.get(pos)
+        }
+    }
+
+    pub fn protect(&mut self, range: Range<Pos>) {
+        self.protected.push(range);
+    }
+
+    // Protects the instructions that static analysis finds in the current memory image.
+    pub fn protect_code(&mut self) {
+        let len = self.memory.cells().map(|(pos, _)| pos + 1usize).max().unwrap_or_default();
+        let image = self.read_range(&Pos::zero(), len.to_usize().expect("Program fits in memory."));
+
+        for range in crate::lint::code_ranges(&image, &self.opcodes) {
+            self.protect(range.start.into()..range.end.into());
+        }
+    }
+
+    pub fn protected(&self) -> &[Range<Pos>] {
+        &self.protected
+    }
+
+    pub fn write_fault(&self) -> Option<&WriteFault> {
+        self.write_fault.as_ref()
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    // Memory and pending inputs are converted too. Under `TrapI64` a word that does not fit fails
+    // with `Overflow` and leaves the state unchanged.
+    pub fn set_semantics(&mut self, semantics: Semantics) -> Result<(), YieldReason> {
+        let memory = self.memory.0.iter()
+            .map(|(pos, w)| Ok((pos.clone(), semantics.apply(w.clone())?)))
+            .collect::<Result<_, YieldReason>>()?;
+
+        let inputs = self.inputs.iter().map(|w| semantics.apply(w.clone())).collect::<Result<_, _>>()?;
+
+        self.memory = Memory(memory);
+        self.inputs = inputs;
+        self.semantics = semantics;
+        Ok(())
+    }
+
+    pub fn keep_history(&mut self, len: usize) {
+        self.history_len = len;
+        while self.history.len() > len {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn history(&self) -> impl Iterator<Item=&Executed> {
+        self.history.iter()
+    }
+
+    pub(crate) fn opcodes(&self) -> &Opcodes {
+        &self.opcodes
+    }
+
+    pub(crate) fn cells(&self) -> impl Iterator<Item=(&Pos, &Word)> {
+        self.memory.cells()
+    }
+
     pub fn outputs(s: &str, inputs: impl Iterator<Item=InputWord>) -> Vec<Word> {
         let mut state = Self::new_from_str(s, inputs);
         state.run_to_halt();
@@ -183,19 +584,144 @@ impl State {
         }
     }
 
+    // `Reached` and `InputRequested` are checked before every instruction including the first, so a
+    // stop on them repeats until the caller steps past it or supplies input.
+    pub fn run_until(&mut self, until: &[Until]) -> (Vec<Word>, Event) {
+        let produced = self.outputs.len();
+
+        let event = loop {
+            if let Some(event) = self.before_step(until) {
+                break event;
+            }
+
+            if let Err(reason) = self.step() {
+                break Event::Yield(reason);
+            }
+
+            if let Some(event) = self.after_step(until, self.outputs.len() - produced) {
+                break event;
+            }
+        };
+
+        let outputs = self.outputs.to_vec();
+        self.outputs.clear();
+
+        (outputs, event)
+    }
+
+    fn before_step(&self, until: &[Until]) -> Option<Event> {
+        let wants_input = || self.inputs.is_empty() && matches!(Insn::parse(&self.opcodes, &self.memory, &self.ip), Some(Insn { opcode: Opcode::Input, .. }));
+
+        until.iter().find_map(|u| match u {
+            Until::Reached(pos) if pos == &self.ip => Some(Event::Reached(pos.clone())),
+            Until::InputRequested if wants_input() => Some(Event::InputRequested),
+            _ => None
+        })
+    }
+
+    fn after_step(&self, until: &[Until], produced: usize) -> Option<Event> {
+        until.iter().find_map(|u| match u {
+            Until::Outputs(n) if produced >= *n => Some(Event::Outputs),
+            Until::WriteTo(pos) if self.last_write.as_ref() == Some(pos) => Some(Event::Write(pos.clone())),
+            _ => None
+        })
+    }
+
     pub fn write_memory(&mut self, pos: InputWord, value: InputWord) {
-        let mem_cell = self.memory.get_mut(pos.to_biguint().expect("Correct address!"));
-        *mem_cell = value.into();
+        self.try_write_memory(&pos.into(), value.into()).expect("Correct address!")
+    }
+
+    pub fn try_read_memory(&self, pos: &Word) -> Result<Word, YieldReason> {
+        let pos = pos.to_biguint().ok_or(YieldReason::NegativeAddress)?;
+        Ok(self.read(&pos))
+    }
+
+    pub fn try_write_memory(&mut self, pos: &Word, value: Word) -> Result<(), YieldReason> {
+        let pos = pos.to_biguint().ok_or(YieldReason::NegativeAddress)?;
+        self.write(pos, value);
+        Ok(())
+    }
+
+    // The write path of the built-in instructions, for extension handlers.
+    pub fn checked_write(&mut self, pos: Pos, value: Word) -> Result<(), YieldReason> {
+        let value = self.semantics.apply(value)?;
+        self.store(pos, value)
+    }
+
+    pub fn read(&self, pos: &Pos) -> Word {
+        match self.devices.iter().find(|(range, _)| range.contains(pos)) {
+            Some((range, device)) => device.peek(&(pos - &range.start)),
+            None => self.memory.get(pos)
+        }
+    }
+
+    pub fn write(&mut self, pos: Pos, value: Word) {
+        match self.device_at(&pos) {
+            Some((offset, device)) => device.write(&offset, value),
+            None => *self.memory.get_mut(pos) = value
+        }
+    }
+
+    pub fn read_range(&self, start: &Pos, len: usize) -> Vec<Word> {
+        (0..len).map(|i| self.read(&(start + i))).collect()
+    }
+
+    pub fn write_range(&mut self, start: &Pos, values: impl IntoIterator<Item=Word>) {
+        for (i, value) in values.into_iter().enumerate() {
+            self.write(start + i, value);
+        }
+    }
+
+    pub fn set_ip(&mut self, ip: Pos) {
+        self.ip = ip;
+    }
+
+    pub fn set_rel_base(&mut self, rel_base: Word) {
+        self.rel_base = rel_base;
+    }
+
+    pub fn emit(&mut self, value: Word) {
+        self.outputs.push(value);
+    }
+
+    pub fn take_input(&mut self) -> Option<Word> {
+        self.inputs.pop()
     }
 
     pub fn supply_input(&mut self, input: InputWord) {
-        self.inputs.insert(0, input.into())
+        self.supply_word(input.into())
+    }
+
+    // Panics past the input limits; use `try_supply_word` when inputs are untrusted.
+    pub fn supply_word(&mut self, input: Word) {
+        self.try_supply_word(input).expect("Inputs within limits!")
+    }
+
+    pub fn try_supply_word(&mut self, input: Word) -> Result<(), YieldReason> {
+        let input = self.semantics.apply(input)?;
+        Limits::check_queue(self.limits.inputs, self.inputs.len(), Limit::Inputs)?;
+        self.limits.check_word(&input)?;
+        self.inputs.insert(0, input);
+        Ok(())
+    }
+
+    pub fn take_output(&mut self) -> Option<Word> {
+        (!self.outputs.is_empty()).as_some_from(|| self.outputs.remove(0))
     }
 
     pub fn step(&mut self) -> Result<(), YieldReason> {
+        self.last_write = None;
         (!self.halted).ok_or(YieldReason::Halt)?;
 
-        let insn = Insn::parse(&self.memory, &self.ip).ok_or(YieldReason::IncorrectOpcode)?;
+        let insn = Insn::parse(&self.opcodes, &self.memory, &self.ip).ok_or(YieldReason::IncorrectOpcode)?;
+
+        let executed = (self.history_len > 0).as_some_from(|| Executed {
+            ip: self.ip.clone(),
+            rel_base: self.rel_base.clone(),
+            words: (0..=insn.args.len()).map(|i| self.memory.get(&(&self.ip + i))).collect()
+        });
+
+        let before = self.calls.is_some().as_some_from(|| (self.ip.clone(), self.rel_base.clone()));
 
         let npos = match insn.opcode {
             Opcode::Halt => {
@@ -208,25 +734,29 @@ impl State {
                 let op2 = self.in_arg(insn.get_arg(1)?)?;
                 let dest = self.out_arg(insn.get_arg(2)?)?;
 
-                *dest = match insn.opcode {
+                let value = match insn.opcode {
                     Opcode::Add => op1 + op2,
                     Opcode::Mul => op1 * op2,
                     _ => unreachable!()
                 };
 
+                self.store(dest, self.semantics.apply(value)?)?;
+
                 None
             },
 
             Opcode::Input => {
-                let input = self.inputs.pop().ok_or(YieldReason::WaitInput)?;
+                let input = self.inputs.last().cloned().ok_or(YieldReason::WaitInput)?;
                 let dest = self.out_arg(insn.get_arg(0)?)?;
-                *dest = input;
+                self.store(dest, input)?;
+                self.inputs.pop();
 
                 None
             },
 
             Opcode::Output => {
                 let op = self.in_arg(insn.get_arg(0)?)?;
+                Limits::check_queue(self.limits.outputs, self.outputs.len(), Limit::Outputs)?;
                 self.outputs.push(op);
 
                 None
@@ -256,39 +786,103 @@ impl State {
                     _ => unreachable!()
                 };
 
-                *dest = (flag as isize).to_bigint().expect("Never fails.");
+                self.store(dest, (flag as isize).to_bigint().expect("Never fails."))?;
 
                 None
             },
 
             Opcode::AdjRelBase => {
                 let op = self.in_arg(insn.get_arg(0)?)?;
-                self.rel_base += op;
+                let rel_base = self.semantics.apply(&self.rel_base + op)?;
+                self.limits.check_word(&rel_base)?;
+                self.rel_base = rel_base;
 
                 None
+            },
+
+            Opcode::Extension(ref handler) => {
+                let operands: Result<Vec<_>, _> = insn.args.iter().cloned().map(|arg| self.operand(arg)).collect();
+                handler.clone()(self, &operands?)?
             }
         };
 
         self.ip = npos.unwrap_or_else(|| self.ip.clone() + 1usize + insn.args.len());
+
+        if let (Some(calls), Some((ip, rel_base))) = (self.calls.as_mut(), before) {
+            calls.observe(&insn.opcode, ip, 1 + insn.args.len(), &rel_base, &self.ip, &self.rel_base);
+        }
+
+        for (_, device) in self.devices.iter_mut() {
+            device.tick();
+        }
+
+        if let Some(executed) = executed {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+
+            self.history.push_back(executed);
+        }
+
         Ok(())
     }
 
-    fn in_arg(&self, arg: Arg) -> Result<Word, YieldReason> {
+    fn in_arg(&mut self, arg: Arg) -> Result<Word, YieldReason> {
         match arg {
             Arg::Immediate(val) => Ok(val),
-            Arg::Position(pos) => Ok(self.memory.get(&pos)),
-            Arg::Relative(offset) => Ok(self.memory.get(&self.rel_addr(offset)?))
+            Arg::Position(pos) => Ok(self.fetch(&pos)),
+            Arg::Relative(offset) => {
+                let pos = self.rel_addr(offset)?;
+                Ok(self.fetch(&pos))
+            }
         }
     }
 
-    fn out_arg(&mut self, arg: Arg) -> Result<&mut Word, YieldReason> {
+    fn operand(&mut self, arg: Arg) -> Result<Operand, YieldReason> {
+        let address = match arg {
+            Arg::Immediate(_) => None,
+            Arg::Position(ref pos) => Some(pos.clone()),
+            Arg::Relative(ref offset) => Some(self.rel_addr(offset.clone())?)
+        };
+
+        Ok(Operand { value: self.in_arg(arg)?, address })
+    }
+
+    fn out_arg(&self, arg: Arg) -> Result<Pos, YieldReason> {
         match arg {
             Arg::Immediate(_) => Err(YieldReason::IncorrectOpcode),
-            Arg::Position(pos) => Ok(self.memory.get_mut(pos)),
-            Arg::Relative(offset) => Ok(self.memory.get_mut(self.rel_addr(offset)?))
+            Arg::Position(pos) => Ok(pos),
+            Arg::Relative(offset) => self.rel_addr(offset)
         }
     }
 
+    fn store(&mut self, pos: Pos, value: Word) -> Result<(), YieldReason> {
+        if self.protected.iter().any(|range| range.contains(&pos)) {
+            self.write_fault = Some(WriteFault { ip: self.ip.clone(), target: pos });
+            return Err(YieldReason::WriteProtected);
+        }
+
+        if self.limits.max_address.as_ref().is_some_and(|max| &pos > max) {
+            return Err(YieldReason::LimitExceeded(Limit::Address));
+        }
+
+        self.limits.check_word(&value)?;
+
+        if let Some((offset, device)) = self.device_at(&pos) {
+            device.write(&offset, value);
+            self.last_write = Some(pos);
+            return Ok(());
+        }
+
+        if self.limits.cells.is_some_and(|cells| self.memory.0.len() >= cells && !self.memory.0.contains_key(&pos)) {
+            return Err(YieldReason::LimitExceeded(Limit::Cells));
+        }
+
+        *self.memory.get_mut(pos.clone()) = value;
+        self.last_write = Some(pos);
+        Ok(())
+    }
+
     fn rel_addr(&self, offset: Word) -> Result<Pos, YieldReason> {
         (self.rel_base.clone() + offset).to_biguint().ok_or(YieldReason::NegativeAddress)
     }
@@ -320,3 +914,199 @@ fn bigint_another() {
     let outputs = State::outputs(&code, [0isize].into_iter().cloned());
     assert_eq!(itertools::join(&outputs, ","), "1125899906842624");
 }
+
+#[test]
+fn extension_opcodes() {
+    let mut state = State::new_from_str("142,21,7,4,7,99,0,0", empty());
+    state.register_opcode(42, 2, |state, ops| {
+        let dest = ops[1].address.clone().ok_or(YieldReason::IncorrectOpcode)?;
+        state.checked_write(dest, &ops[0].value * 2)?;
+        Ok(None)
+    });
+
+    let mut protected = state.clone();
+    assert_eq!(state.run_to_yield(), (vec![42.into()], YieldReason::Halt));
+
+    protected.protect(Pos::from(6usize)..Pos::from(8usize));
+    assert_eq!(protected.run_to_yield(), (vec![], YieldReason::WriteProtected));
+}
+
+#[test]
+fn extension_trap() {
+    let mut state = State::new_from_str("150,1,150,0,99", empty());
+    state.register_opcode(50, 1, |_, ops| {
+        match ops[0].value.to_isize() {
+            Some(0) => Err(YieldReason::Trap(50)),
+            _ => Ok(None)
+        }
+    });
+
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::Trap(50)));
+    assert!(YieldReason::Trap(50).is_fault());
+}
+
+#[test]
+#[should_panic(expected = "Opcodes are the two lowest decimal digits!")]
+fn extension_opcode_fits_two_digits() {
+    State::new_from_str("142,99", empty()).register_opcode(142, 0, |_, _| Ok(None));
+}
+
+#[test]
+fn run_until_events() {
+    let mut state = State::new_from_str("3,20,104,1,104,2,104,3,1001,20,1,20,1105,1,0", empty());
+
+    assert_eq!(state.run_until(&[Until::InputRequested]), (vec![], Event::InputRequested));
+    assert_eq!(state.run_until(&[Until::Reached(0usize.into())]), (vec![], Event::Reached(0usize.into())));
+
+    state.supply_input(7);
+    assert_eq!(state.run_until(&[Until::Outputs(2)]), (vec![1.into(), 2.into()], Event::Outputs));
+    assert_eq!(state.run_until(&[Until::WriteTo(20usize.into())]), (vec![3.into()], Event::Write(20usize.into())));
+    assert_eq!(state.run_until(&[Until::Reached(0usize.into())]), (vec![], Event::Reached(0usize.into())));
+    assert_eq!(state.run_until(&[Until::Outputs(1), Until::InputRequested]), (vec![], Event::InputRequested));
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::WaitInput));
+
+    state.supply_input(9);
+    assert_eq!(state.run_until(&[Until::Outputs(5), Until::InputRequested]), (vec![1.into(), 2.into(), 3.into()], Event::InputRequested));
+}
+
+#[test]
+fn word_semantics() {
+    let code = "1102,4294967296,4294967296,9,1001,9,-1,10,99,0,0";
+    let run = |semantics| {
+        let mut state = State::new_from_str(code, empty());
+        state.set_semantics(semantics).unwrap();
+        let (_, reason) = state.run_to_yield();
+        (reason, state.read(&9usize.into()), state.read(&10usize.into()))
+    };
+
+    let big = Word::from(1) << 64;
+    assert_eq!(run(Semantics::Unbounded), (YieldReason::Halt, big.clone(), big - 1));
+    assert_eq!(run(Semantics::TrapI64), (YieldReason::Overflow, 0.into(), 0.into()));
+    assert_eq!(run(Semantics::WrapI64), (YieldReason::Halt, 0.into(), (-1).into()));
+    assert_eq!(run(Semantics::WrapI32), (YieldReason::Halt, 0.into(), (-1).into()));
+
+    assert_eq!(Semantics::WrapI32.apply(Word::from(i32::MAX) + 1), Ok(i32::MIN.into()));
+    assert_eq!(Semantics::WrapI64.apply(Word::from(i64::MIN) - 1), Ok(i64::MAX.into()));
+
+    // Literals and inputs are converted like results.
+    let mut state = State::new_from_str("104,4294967296,1107,4294967296,1,11,4,11,3,12,99,0,0", empty());
+    state.set_semantics(Semantics::WrapI32).unwrap();
+    state.supply_word(Word::from(1) << 32);
+    assert_eq!(state.run_to_yield(), (vec![0.into(), 1.into()], YieldReason::Halt));
+    assert_eq!(state.read(&12usize.into()), 0.into());
+
+    let mut state = State::new_from_str("99", empty());
+    state.set_semantics(Semantics::TrapI64).unwrap();
+    assert_eq!(state.try_supply_word(Word::from(1) << 100), Err(YieldReason::Overflow));
+
+    let mut state = State::new_from_str("104,1267650600228229401496703205376,99", empty());
+    assert_eq!(state.set_semantics(Semantics::TrapI64), Err(YieldReason::Overflow));
+    assert_eq!(state.run_to_yield(), (vec![Word::from(1) << 100], YieldReason::Halt));
+}
+
+#[test]
+fn loads_big_literals_and_comments() {
+    let source = "# doubles a big literal\n1102, 2,\n  36893488147419103232 ,7, # 2^65\n\n4,7,99,0,\n";
+    let mut state = State::load(source.as_bytes(), empty()).unwrap();
+    assert_eq!(state.run_to_yield(), (vec![Word::from(1) << 66], YieldReason::Halt));
+
+    let error = |s: &str| match load_words(s.as_bytes()) {
+        Err(LoadError::Cell { index, line, .. }) => Some((index, line)),
+        _ => None
+    };
+
+    assert_eq!(error("1,2,\n3 4,5"), Some((2, 2)));
+    assert_eq!(error("1,,2"), Some((1, 1)));
+    assert_eq!(error("1,x"), Some((1, 1)));
+}
+
+#[test]
+fn loads_labels() {
+    let (words, labels) = load_labelled("# start:\n3,7, # loop:\n1005\n# skip this\n,7,2, # end:".as_bytes()).unwrap();
+    assert_eq!(words.len(), 5);
+    assert_eq!(labels.into_iter().collect::<Vec<_>>(), [("end".to_string(), 5), ("loop".to_string(), 2), ("start".to_string(), 0)]);
+}
+
+#[test]
+fn host_memory_access() {
+    let mut state = State::new_from_str("1,0,0,0,99", empty());
+
+    assert_eq!(state.try_write_memory(&(-1).into(), 5.into()), Err(YieldReason::NegativeAddress));
+    assert_eq!(state.try_read_memory(&(-1).into()), Err(YieldReason::NegativeAddress));
+
+    let big = Word::from(1) << 80;
+    state.write_range(&1usize.into(), vec![5.into(), 6.into(), 7.into()]);
+    state.try_write_memory(&5.into(), big.clone()).unwrap();
+    state.try_write_memory(&6.into(), big.clone()).unwrap();
+    assert_eq!(state.read_range(&0usize.into(), 4), vec![1.into(), 5.into(), 6.into(), 7.into()]);
+
+    state.set_rel_base(3.into());
+    state.supply_word(big.clone());
+    state.write_range(&7usize.into(), vec![203.into(), 4.into(), 99.into()]);
+    state.set_ip(7usize.into());
+
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::Halt));
+    assert_eq!(state.try_read_memory(&7.into()), Ok(big));
+    assert_eq!(state.ip(), &Pos::from(10usize));
+}
+
+#[test]
+fn resource_limits() {
+    let run = |code: &str, limits: Limits| {
+        let mut state = State::new_from_str(code, empty());
+        state.set_limits(limits);
+        state.run_to_yield().1
+    };
+
+    let exceeded = YieldReason::LimitExceeded;
+    let spray = "1101,0,0,1000,1101,0,0,2000,99";
+
+    assert_eq!(run(spray, Limits::default()), YieldReason::Halt);
+    assert_eq!(run(spray, Limits { cells: Some(10), ..Limits::default() }), exceeded(Limit::Cells));
+    assert_eq!(run(spray, Limits { max_address: Some(1500usize.into()), ..Limits::default() }), exceeded(Limit::Address));
+
+    let square = "1002,7,7,7,1105,1,0,3";
+    assert_eq!(run(square, Limits { word_bits: Some(64), ..Limits::default() }), exceeded(Limit::WordBits));
+    assert_eq!(run("104,1,1105,1,0", Limits { outputs: Some(3), ..Limits::default() }), exceeded(Limit::Outputs));
+
+    let mut state = State::new_from_str("99", empty());
+    state.set_limits(Limits { inputs: Some(1), ..Limits::default() });
+    assert_eq!(state.try_supply_word(1.into()), Ok(()));
+    assert_eq!(state.try_supply_word(2.into()), Err(exceeded(Limit::Inputs)));
+}
+
+#[test]
+fn write_protection() {
+    let mut state = State::new_from_str("1101,1,1,13,109,1,21101,7,7,0,99,0,0,0", empty());
+    state.protect_code();
+    assert_eq!(state.protected(), &[Pos::zero()..Pos::from(11usize)]);
+
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::WriteProtected));
+    assert_eq!(state.write_fault(), Some(&WriteFault { ip: 6usize.into(), target: 1usize.into() }));
+    assert_eq!(state.read(&13usize.into()), 2.into());
+
+    let mut state = State::new_from_str("3,20,99", [5isize].iter().cloned());
+    state.protect(Pos::from(20usize)..Pos::from(30usize));
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::WriteProtected));
+    assert_eq!(state.pending_inputs(), vec![5.into()]);
+}
+
+#[test]
+fn call_stack() {
+    let code = "109,100,21101,9,0,1,1105,1,11,99,0,109,1,21101,20,0,1,1105,1,25,109,-1,2105,1,1,109,1,3,200,109,-1,2105,1,1";
+    let mut state = State::new_from_str(code, empty());
+    state.track_calls();
+
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::WaitInput));
+
+    let frames: Vec<_> = state.backtrace().into_iter().map(|f| (f.call_site, f.entry, f.return_address, f.base)).collect();
+    assert_eq!(frames, vec![
+        (17usize.into(), 25usize.into(), 20usize.into(), 102.into()),
+        (6usize.into(), 11usize.into(), 9usize.into(), 101.into())
+    ]);
+
+    state.supply_input(0);
+    assert_eq!(state.run_to_yield(), (vec![], YieldReason::Halt));
+    assert!(state.backtrace().is_empty());
+}
+
@@ -0,0 +1,359 @@
+use std::fmt;
+
+// Just enough JSON for protocol messages: objects keep their key order so output is reproducible.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: &'static str
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.message)
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T, ParseError> {
+        Err(ParseError { offset: self.pos, message })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(b) if b == byte => {
+                self.pos += 1;
+                Ok(())
+            },
+            _ => self.error("unexpected character")
+        }
+    }
+
+    fn literal(&mut self, text: &str, value: Json) -> Result<Json, ParseError> {
+        if self.bytes[self.pos..].starts_with(text.as_bytes()) {
+            self.pos += text.len();
+            Ok(value)
+        } else {
+            self.error("unknown literal")
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, ParseError> {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|&b| b.is_ascii_digit() || b"+-.eE".contains(&b)) {
+            self.pos += 1;
+        }
+
+        let text = std::str::from_utf8(&self.bytes[start..self.pos]).expect("ASCII digits.");
+        text.parse().map(Json::Number).or_else(|_| self.error("bad number"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let digits = self.bytes.get(self.pos..self.pos + 4).and_then(|d| std::str::from_utf8(d).ok());
+        let code = digits.and_then(|d| u32::from_str_radix(d, 16).ok());
+        self.pos += 4;
+        code.map_or_else(|| self.error("bad unicode escape"), Ok)
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+
+        loop {
+            let b = match self.bytes.get(self.pos) {
+                Some(&b) => b,
+                None => return self.error("unterminated string")
+            };
+
+            self.pos += 1;
+
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self.bytes.get(self.pos).cloned();
+                    self.pos += 1;
+
+                    let c = match escaped {
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let mut code = self.hex4()?;
+
+                            // A high surrogate only combines with a following low one; otherwise the
+                            // next escape is left alone and the lone surrogate becomes U+FFFD.
+                            if (0xd800..0xdc00).contains(&code) && self.bytes[self.pos..].starts_with(b"\\u") {
+                                let start = self.pos;
+                                self.pos += 2;
+
+                                match self.hex4()? {
+                                    low @ 0xdc00..=0xdfff => code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00),
+                                    _ => self.pos = start
+                                }
+                            }
+
+                            std::char::from_u32(code).unwrap_or('\u{fffd}')
+                        },
+                        Some(c @ b'"') | Some(c @ b'\\') | Some(c @ b'/') => c as char,
+                        _ => return self.error("bad escape")
+                    };
+
+                    bytes.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                b => bytes.push(b)
+            }
+        }
+
+        String::from_utf8(bytes).or_else(|_| self.error("invalid utf-8"))
+    }
+
+    fn value(&mut self) -> Result<Json, ParseError> {
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+
+                loop {
+                    items.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        },
+                        _ => return self.error("expected , or ]")
+                    }
+                }
+            },
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+
+                loop {
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(fields));
+                        },
+                        _ => return self.error("expected , or }")
+                    }
+                }
+            },
+            Some(b) if b == b'-' || b.is_ascii_digit() => self.number(),
+            _ => self.error("expected a value")
+        }
+    }
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Json, ParseError> {
+        let mut parser = Parser { bytes: s.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+
+        match parser.peek() {
+            None => Ok(value),
+            Some(_) => parser.error("trailing characters")
+        }
+    }
+
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 9e15 => Some(*n as i64),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(n: i64) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self {
+        Json::Array(items)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => match self.as_i64() {
+                Some(i) => write!(f, "{}", i),
+                None if n.is_finite() => write!(f, "{}", n),
+                None => write!(f, "null")
+            },
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", item)?;
+                }
+
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[test]
+fn parses_and_prints() {
+    let text = r#" { "seq": 1, "args": [true, null, -2.5, 1e3], "s": "a\"b\\né😀", "o": {} } "#;
+    let json = Json::parse(text).unwrap();
+
+    assert_eq!(json.get("seq").and_then(Json::as_i64), Some(1));
+    assert_eq!(json.get("s").and_then(Json::as_str), Some("a\"b\\né😀"));
+    assert_eq!(json.to_string(), r#"{"seq":1,"args":[true,null,-2.5,1000],"s":"a\"b\\né😀","o":{}}"#);
+    assert_eq!(Json::parse(&json.to_string()), Ok(json));
+
+    assert_eq!(Json::parse("[1,]").unwrap_err().offset, 3);
+    assert!(Json::parse("{\"a\" 1}").is_err());
+    assert!(Json::parse("1 2").is_err());
+}
+
+#[test]
+fn lone_surrogates_are_replaced() {
+    assert_eq!(Json::parse(r#""\ud800\u0041""#), Ok(Json::String("\u{fffd}A".to_string())));
+    assert_eq!(Json::parse(r#""\ud800\ud800\udc00""#), Ok(Json::String("\u{fffd}\u{10000}".to_string())));
+    assert_eq!(Json::parse(r#""\udc00x""#), Ok(Json::String("\u{fffd}x".to_string())));
+}
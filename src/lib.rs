@@ -1,3 +1,25 @@
 pub mod intcode;
 pub mod intcode_full;
 pub mod permutations;
+pub mod ascii;
+pub mod rng;
+pub mod difftest;
+pub mod symbolic;
+pub mod transcript;
+pub mod memdiff;
+pub mod disasm;
+pub mod coredump;
+pub mod stream;
+pub mod callstack;
+pub mod gdbstub;
+pub mod json;
+pub mod dap;
+pub mod taint;
+pub mod diverge;
+pub mod minimize;
+pub mod devices;
+pub mod batch;
+pub mod driver;
+pub mod search;
+pub mod lint;
+pub mod cli;
@@ -0,0 +1,370 @@
+use std::fmt;
+use std::ops::Range;
+use std::collections::BTreeSet;
+
+use boolinator::Boolinator;
+use num_traits::cast::ToPrimitive;
+
+use crate::intcode_full::{Opcode, Opcodes, Word, load_words};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Warning,
+    Error
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Issue {
+    UnknownOpcode(Word),
+    BadMode { param: usize, mode: usize },
+    ImmediateDestination { param: usize },
+    Truncated,
+    JumpOutOfProgram { target: Word },
+    Unreachable { len: usize },
+    // Found only on a branch that folding ruled out, reachable if a relative-mode write changes its condition.
+    Speculative(Box<Issue>)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub address: usize,
+    pub issue: Issue
+}
+
+struct Decoded {
+    opcode: Opcode,
+    modes: Vec<usize>,
+    args: Vec<Word>
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Issue::Unreachable { .. } | Issue::Speculative(_) => Severity::Warning,
+            _ => Severity::Error
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::UnknownOpcode(w) => write!(f, "unknown opcode in {}", w),
+            Issue::BadMode { param, mode } => write!(f, "parameter {} has mode {}", param + 1, mode),
+            Issue::ImmediateDestination { param } => write!(f, "destination parameter {} is in immediate mode", param + 1),
+            Issue::Truncated => write!(f, "instruction runs past the end of the program"),
+            Issue::JumpOutOfProgram { target } => write!(f, "jump target {} is outside the program", target),
+            Issue::Unreachable { len } => write!(f, "{} words of unreachable code", len),
+            Issue::Speculative(issue) => write!(f, "{} (if a relative-mode write changes a branch)", issue)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.issue.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        };
+
+        write!(f, "{:>6}: {}: {}", self.address, severity, self.issue)
+    }
+}
+
+fn destinations(opcode: &Opcode) -> &'static [usize] {
+    match opcode {
+        Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => &[2],
+        Opcode::Input => &[0],
+        _ => &[]
+    }
+}
+
+fn decode(program: &[Word], opcodes: &Opcodes, address: usize) -> Result<Decoded, Issue> {
+    let insn = &program[address];
+    let code = insn.to_usize().ok_or_else(|| Issue::UnknownOpcode(insn.clone()))?;
+    let (opcode, arg_count) = opcodes.lookup(code % 100).ok_or_else(|| Issue::UnknownOpcode(insn.clone()))?;
+
+    let args = program.get(address + 1..address + 1 + arg_count).ok_or(Issue::Truncated)?.to_vec();
+    let modes = (0..arg_count).scan(code / 100, |m, _| {
+        let mode = *m % 10;
+        *m /= 10;
+        Some(mode)
+    });
+
+    Ok(Decoded { opcode, modes: modes.collect(), args })
+}
+
+fn check_modes(decoded: &Decoded) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for (param, &mode) in decoded.modes.iter().enumerate() {
+        if mode > 2 {
+            issues.push(Issue::BadMode { param, mode });
+        } else if mode == 1 && destinations(&decoded.opcode).contains(&param) {
+            issues.push(Issue::ImmediateDestination { param });
+        }
+    }
+
+    issues
+}
+
+enum Flow {
+    Stop,
+    Next,
+    Jump(Option<Word>),
+    Branch(Option<Word>)
+}
+
+// Conditions read from cells that no reachable instruction writes are folded to their initial value,
+// unless some write goes to an address that is not known statically.
+fn flow(decoded: &Decoded, program: &[Word], patched: &BTreeSet<usize>, fold: bool) -> Flow {
+    let (truthy, condition, target) = match decoded.opcode {
+        Opcode::Halt => return Flow::Stop,
+        Opcode::Extension(_) => return Flow::Branch(None),
+        Opcode::JumpIfTrue => (true, &decoded.args[0], &decoded.args[1]),
+        Opcode::JumpIfFalse => (false, &decoded.args[0], &decoded.args[1]),
+        _ => return Flow::Next
+    };
+
+    let target = (decoded.modes[1] == 1).as_some_from(|| target.clone());
+
+    let condition = match decoded.modes[0] {
+        1 => Some(condition),
+        0 if fold => condition.to_usize().filter(|a| !patched.contains(a)).and_then(|a| program.get(a)),
+        _ => None
+    };
+
+    match condition {
+        None => Flow::Branch(target),
+        Some(c) if (c != &Word::from(0)) == truthy => Flow::Jump(target),
+        Some(_) => Flow::Next
+    }
+}
+
+struct Traversal {
+    diagnostics: Vec<Diagnostic>,
+    covered: Vec<bool>,
+    written: BTreeSet<usize>,
+    // A relative-mode destination or an extension opcode may write anywhere.
+    unknown_writes: bool,
+    indirect: bool
+}
+
+// Instructions overlapping `patched` addresses are rewritten at runtime, so their static decoding
+// is not trusted: they produce no diagnostics and their jump targets count as unknown.
+fn traverse(program: &[Word], opcodes: &Opcodes, patched: &BTreeSet<usize>, fold: bool) -> Traversal {
+    let mut t = Traversal {
+        diagnostics: Vec::new(),
+        covered: vec![false; program.len()],
+        written: BTreeSet::new(),
+        unknown_writes: false,
+        indirect: false
+    };
+
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![0usize];
+
+    while let Some(address) = pending.pop() {
+        if address >= program.len() || !visited.insert(address) {
+            continue;
+        }
+
+        t.covered[address] = true;
+
+        if patched.contains(&address) {
+            t.indirect = true;
+            continue;
+        }
+
+        let decoded = match decode(program, opcodes, address) {
+            Ok(decoded) => decoded,
+            Err(issue) => {
+                t.diagnostics.push(Diagnostic { address, issue });
+                continue;
+            }
+        };
+
+        let next = address + 1 + decoded.args.len();
+        for c in t.covered[address..next].iter_mut() {
+            *c = true;
+        }
+
+        // A jump whose target cell is patched still has a known condition, only the target is unknown.
+        let target_cell = match decoded.opcode {
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => Some(address + 2),
+            _ => None
+        };
+
+        let trusted = patched.range(address..next).all(|&a| Some(a) == target_cell);
+        let target_patched = target_cell.is_some_and(|a| patched.contains(&a));
+
+        if patched.range(address..next).next().is_none() {
+            t.diagnostics.extend(check_modes(&decoded).into_iter().map(|issue| Diagnostic { address, issue }));
+        }
+
+        for &param in destinations(&decoded.opcode) {
+            match (decoded.modes[param], decoded.args[param].to_usize()) {
+                (0, Some(dest)) => {
+                    t.written.insert(dest);
+                },
+                (2, _) => t.unknown_writes = true,
+                _ => ()
+            }
+        }
+
+        if let Opcode::Extension(_) = decoded.opcode {
+            t.unknown_writes = true;
+        }
+
+        let (fallthrough, target) = match flow(&decoded, program, patched, fold) {
+            Flow::Stop => (false, None),
+            Flow::Next => (true, None),
+            _ if !trusted => (true, Some(None)),
+            Flow::Jump(_) if target_patched => (false, Some(None)),
+            Flow::Branch(_) if target_patched => (true, Some(None)),
+            Flow::Jump(target) => (false, Some(target)),
+            Flow::Branch(target) => (true, Some(target))
+        };
+
+        if fallthrough {
+            pending.push(next);
+        }
+
+        match target {
+            Some(Some(target)) => match target.to_usize().filter(|&t| t < program.len()) {
+                Some(t) => pending.push(t),
+                None => t.diagnostics.push(Diagnostic { address, issue: Issue::JumpOutOfProgram { target } })
+            },
+            Some(None) => t.indirect = true,
+            None => ()
+        }
+    }
+
+    t
+}
+
+fn settle(program: &[Word], opcodes: &Opcodes, fold: bool) -> (Traversal, BTreeSet<usize>) {
+    let mut patched = BTreeSet::new();
+
+    loop {
+        let t = traverse(program, opcodes, &patched, fold);
+        if t.written.is_subset(&patched) {
+            return (t, patched);
+        }
+
+        patched.extend(t.written);
+    }
+}
+
+// Statically reachable instruction words that no instruction writes to directly. Code behind
+// indirect jumps is not traversed, so then every decodable word is treated as a possible writer.
+// Relative-mode and extension writes are not resolved; protecting the ranges traps them at runtime.
+pub fn code_ranges(program: &[Word], opcodes: &Opcodes) -> Vec<Range<usize>> {
+    let (t, patched) = settle(program, opcodes, true);
+    let (t, mut patched) = if t.unknown_writes { settle(program, opcodes, false) } else { (t, patched) };
+
+    if t.indirect {
+        for decoded in (0..program.len()).filter_map(|a| decode(program, opcodes, a).ok()) {
+            for &param in destinations(&decoded.opcode) {
+                if let (0, Some(dest)) = (decoded.modes[param], decoded.args[param].to_usize()) {
+                    patched.insert(dest);
+                }
+            }
+        }
+    }
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for address in (0..program.len()).filter(|a| t.covered[*a] && !patched.contains(a)) {
+        match ranges.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => ranges.push(address..address + 1)
+        }
+    }
+
+    ranges
+}
+
+pub fn lint(program: &[Word]) -> Vec<Diagnostic> {
+    lint_with(program, &Opcodes::default())
+}
+
+// Lints a program for a machine with extension opcodes registered in `opcodes`.
+pub fn lint_with(program: &[Word], opcodes: &Opcodes) -> Vec<Diagnostic> {
+    let (folded, patched) = settle(program, opcodes, true);
+    let confirmed = folded.diagnostics.clone();
+    let (t, _) = if folded.unknown_writes { settle(program, opcodes, false) } else { (folded, patched) };
+
+    let mut diagnostics: Vec<Diagnostic> = t.diagnostics.into_iter().map(|d| {
+        if confirmed.contains(&d) { d } else { Diagnostic { address: d.address, issue: Issue::Speculative(Box::new(d.issue)) } }
+    }).collect();
+
+    // Indirect jumps may land anywhere, so only programs without them get reachability warnings.
+    if !t.indirect {
+        let mut address = 0;
+        while address < program.len() {
+            let len = t.covered[address..].iter().take_while(|&&c| !c).count();
+
+            let looks_like_code = decode(program, opcodes, address)
+                .is_ok_and(|d| d.args.len() < len && check_modes(&d).is_empty());
+
+            if len > 0 && looks_like_code {
+                diagnostics.push(Diagnostic { address, issue: Issue::Unreachable { len } });
+            }
+
+            address += len.max(1);
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.address);
+    diagnostics
+}
+
+pub fn lint_str(s: &str) -> Option<Vec<Diagnostic>> {
+    let program = load_words(s.as_bytes()).ok()?;
+    Some(lint(&program))
+}
+
+#[test]
+fn flags_bad_programs() {
+    let issues = |s| lint_str(s).unwrap().into_iter().map(|d| (d.address, d.issue)).collect::<Vec<_>>();
+
+    assert_eq!(issues("1101,1,1,5,99,0"), []);
+    assert_eq!(issues("11101,1,1,5,99,0"), [(0, Issue::ImmediateDestination { param: 2 })]);
+    assert_eq!(issues("301,1,1,5,99,0"), [(0, Issue::BadMode { param: 0, mode: 3 })]);
+    assert_eq!(issues("1105,1,4,98,99"), []);
+    assert_eq!(issues("1106,0,4,98,99"), []);
+    assert_eq!(issues("3,9,1005,9,7,99,0,42,0,0"), [(7, Issue::UnknownOpcode(42.into()))]);
+    assert_eq!(issues("1105,1,50,99"), [(0, Issue::JumpOutOfProgram { target: 50.into() }), (3, Issue::Unreachable { len: 1 })]);
+    assert_eq!(issues("99,1101,1,1,0,99"), [(1, Issue::Unreachable { len: 5 })]);
+    assert_eq!(issues("1,0,0"), [(0, Issue::Truncated)]);
+    assert_eq!(issues("1101,1,1,4,0,0,5,99"), []);
+    assert_eq!(issues("3,4,1105,1,0,0"), []);
+
+    // The relative write may clear the condition cell, so both branches are reachable.
+    let speculative = Issue::Speculative(Box::new(Issue::UnknownOpcode(42.into())));
+    assert_eq!(issues("109,-2,21101,0,0,13,1005,11,10,42,99,1"), [(9, speculative)]);
+
+    let mut opcodes = Opcodes::default();
+    opcodes.register(42, 1, |_, _| Ok(None));
+    let program = load_words("42,5,99".as_bytes()).unwrap();
+    assert_eq!(lint(&program), [Diagnostic { address: 0, issue: Issue::UnknownOpcode(42.into()) }]);
+    assert_eq!(lint_with(&program, &opcodes), []);
+}
+
+#[test]
+fn day_11_has_no_errors() {
+    let diagnostics = lint_str(include_str!("bin/inputs/11.txt")).unwrap();
+    assert!(diagnostics.iter().all(|d| d.issue.severity() == Severity::Warning), "{:?}", diagnostics);
+}
+
+#[test]
+fn infers_code_ranges() {
+    let ranges = |s: &str| {
+        let program = load_words(s.as_bytes()).unwrap();
+        code_ranges(&program, &Opcodes::default()).into_iter().map(|r| (r.start, r.end)).collect::<Vec<_>>()
+    };
+
+    assert_eq!(ranges("1101,1,1,9,1005,9,4,99,0,0"), [(0, 8)]);
+    assert_eq!(ranges("3,4,1105,1,0,0"), [(0, 4)]);
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+use std::collections::BTreeSet;
+
+use itertools::join;
+
+use crate::intcode_full::{State, YieldReason, Pos, Word};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Change {
+    pub start: Pos,
+    pub old: Vec<Word>,
+    pub new: Vec<Word>
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diff {
+    pub ip: Option<(Pos, Pos)>,
+    pub rel_base: Option<(Word, Word)>,
+    pub halted: Option<(bool, bool)>,
+    pub inputs: Option<(Vec<Word>, Vec<Word>)>,
+    pub outputs: Option<(Vec<Word>, Vec<Word>)>,
+    pub memory: Vec<Change>
+}
+
+impl Change {
+    pub fn end(&self) -> Pos {
+        &self.start + self.old.len()
+    }
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+    if old != new { Some((old, new)) } else { None }
+}
+
+// Unlike `run_to_yield` this leaves the outputs queued, so they show up in the diff.
+pub fn advance(state: &mut State, inputs: &[Word]) -> YieldReason {
+    inputs.iter().for_each(|i| state.supply_word(i.clone()));
+
+    loop {
+        if let Err(reason) = state.step() {
+            return reason;
+        }
+    }
+}
+
+pub fn diff(old: &State, new: &State) -> Diff {
+    let addresses: BTreeSet<&Pos> = old.cells().chain(new.cells()).map(|(pos, _)| pos).collect();
+
+    let mut memory: Vec<Change> = Vec::new();
+    for pos in addresses {
+        let (o, n) = (old.read(pos), new.read(pos));
+        if o == n {
+            continue;
+        }
+
+        match memory.last_mut() {
+            Some(change) if change.end() == *pos => {
+                change.old.push(o);
+                change.new.push(n);
+            },
+            _ => memory.push(Change { start: pos.clone(), old: vec![o], new: vec![n] })
+        }
+    }
+
+    Diff {
+        ip: changed(old.ip().clone(), new.ip().clone()),
+        rel_base: changed(old.rel_base().clone(), new.rel_base().clone()),
+        halted: changed(old.is_halted(), new.is_halted()),
+        inputs: changed(old.pending_inputs(), new.pending_inputs()),
+        outputs: changed(old.pending_outputs().to_vec(), new.pending_outputs().to_vec()),
+        memory
+    }
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.ip.is_none() && self.rel_base.is_none() && self.halted.is_none()
+            && self.inputs.is_none() && self.outputs.is_none() && self.memory.is_empty()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.old.len() == 1 {
+            write!(f, "{:>12}: {} -> {}", self.start, self.old[0], self.new[0])
+        } else {
+            let range = format!("{}..{}", self.start, self.end());
+            write!(f, "{:>12}: {} -> {}", range, join(&self.old, ","), join(&self.new, ","))
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((old, new)) = &self.ip {
+            writeln!(f, "ip: {} -> {}", old, new)?;
+        }
+
+        if let Some((old, new)) = &self.rel_base {
+            writeln!(f, "rel_base: {} -> {}", old, new)?;
+        }
+
+        if let Some((old, new)) = &self.halted {
+            writeln!(f, "halted: {} -> {}", old, new)?;
+        }
+
+        if let Some((old, new)) = &self.inputs {
+            writeln!(f, "inputs: [{}] -> [{}]", join(old, ","), join(new, ","))?;
+        }
+
+        if let Some((old, new)) = &self.outputs {
+            writeln!(f, "outputs: [{}] -> [{}]", join(old, ","), join(new, ","))?;
+        }
+
+        for change in self.memory.iter() {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn groups_changed_ranges() {
+    let code = "3,20,1001,20,1,21,1002,21,2,22,109,5,4,22,3,20,99";
+    let start = State::new_from_str(code, [4isize].iter().cloned());
+    assert!(diff(&start, &start.clone()).is_empty());
+
+    let mut state = start.clone();
+    while state.step().is_ok() {}
+
+    let d = diff(&start, &state);
+    assert_eq!(d.ip, Some((0usize.into(), 14usize.into())));
+    assert_eq!(d.rel_base, Some((0.into(), 5.into())));
+    assert_eq!(d.halted, None);
+    assert_eq!(d.inputs, Some((vec![4.into()], vec![])));
+    assert_eq!(d.outputs, Some((vec![], vec![10.into()])));
+    assert_eq!(d.memory, vec![Change {
+        start: 20usize.into(),
+        old: vec![0.into(), 0.into(), 0.into()],
+        new: vec![4.into(), 5.into(), 10.into()]
+    }]);
+
+    assert_eq!(d.to_string(), "ip: 0 -> 14\nrel_base: 0 -> 5\ninputs: [4] -> []\noutputs: [] -> [10]\n      20..23: 0,0,0 -> 4,5,10\n");
+}
+
+#[test]
+fn shows_different_outputs() {
+    let start = State::new_from_str("3,20,1002,20,3,20,4,20,99", std::iter::empty());
+    let (mut a, mut b) = (start.clone(), start);
+
+    assert_eq!(advance(&mut a, &[2.into()]), YieldReason::Halt);
+    assert_eq!(advance(&mut b, &[5.into()]), YieldReason::Halt);
+
+    let d = diff(&a, &b);
+    assert_eq!(d.outputs, Some((vec![6.into()], vec![15.into()])));
+    assert!(d.to_string().starts_with("outputs: [6] -> [15]\n"));
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use crate::batch;
+use crate::intcode_full::{State, YieldReason, Word};
+
+// `BudgetExhausted` matches a run that did not yield at all within the step budget.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Failure {
+    Fault,
+    Output(Word),
+    BudgetExhausted
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Outcome {
+    pub outputs: Vec<Word>,
+    // `None` when the step budget ran out before the machine yielded.
+    pub reason: Option<YieldReason>
+}
+
+impl Failure {
+    pub fn matches(&self, outcome: &Outcome) -> bool {
+        match self {
+            Failure::Fault => outcome.reason.is_some_and(YieldReason::is_fault),
+            Failure::Output(w) => outcome.outputs.contains(w),
+            Failure::BudgetExhausted => outcome.reason.is_none()
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Fault => write!(f, "a fault"),
+            Failure::Output(w) => write!(f, "output {}", w),
+            Failure::BudgetExhausted => write!(f, "an exhausted step budget")
+        }
+    }
+}
+
+pub fn run(start: &State, inputs: &[Word], budget: usize) -> Outcome {
+    let mut state = start.clone();
+    inputs.iter().for_each(|w| state.supply_word(w.clone()));
+
+    let reason = (0..budget).find_map(|_| state.step().err());
+
+    let mut outputs = Vec::new();
+    while let Some(output) = state.take_output() {
+        outputs.push(output);
+    }
+
+    Outcome { outputs, reason }
+}
+
+fn chunks<T: Clone>(items: &[T], n: usize) -> Vec<(Vec<T>, Vec<T>)> {
+    let size = items.len().div_ceil(n);
+
+    (0..items.len()).step_by(size).map(|start| {
+        let end = (start + size).min(items.len());
+        (items[start..end].to_vec(), [&items[..start], &items[end..]].concat())
+    }).collect()
+}
+
+// Zeller's ddmin: the result still fails and removing any single item from it makes it pass.
+// Candidates of one round are tested in parallel, the first failing one in order wins.
+pub fn ddmin<T, F>(items: &[T], fails: F) -> Vec<T>
+    where T: Clone + Send + Sync, F: Fn(&[T]) -> bool + Sync
+{
+    if fails(&[]) {
+        return Vec::new();
+    }
+
+    let mut current = items.to_vec();
+    let mut n = 2;
+
+    while current.len() >= 2 {
+        let split = chunks(&current, n);
+        let subsets = split.len();
+
+        let mut candidates: Vec<Vec<T>> = split.iter().map(|(subset, _)| subset.clone()).collect();
+        if subsets > 2 {
+            candidates.extend(split.into_iter().map(|(_, complement)| complement));
+        }
+
+        match batch::find_first(&candidates, |c| fails(c), |&failed| failed) {
+            Some((i, _)) => {
+                current = candidates.swap_remove(i);
+                n = if i < subsets { 2 } else { (n - 1).max(2) };
+            },
+            None if n >= current.len() => break,
+            None => n = (2 * n).min(current.len())
+        }
+    }
+
+    current
+}
+
+pub fn minimize(start: &State, inputs: &[Word], failure: &Failure, budget: usize) -> Vec<Word> {
+    ddmin(inputs, |candidate| failure.matches(&run(start, candidate, budget)))
+}
+
+#[test]
+fn shrinks_to_one_minimal_inputs() {
+    // Sums its inputs and reads a negative address once the running sum is exactly 10.
+    let program = "3,100,1,100,101,101,1008,101,10,102,1005,102,16,1105,1,0,204,-1,99";
+    let start = State::new_from_str(program, std::iter::empty());
+    let inputs: Vec<Word> = [1, 2, 3, 4, 5, 6, 7].iter().map(|&w| Word::from(w)).collect();
+
+    assert!(Failure::Fault.matches(&run(&start, &inputs, 1000)));
+
+    let minimal = minimize(&start, &inputs, &Failure::Fault, 1000);
+    assert!(minimal.len() < inputs.len());
+    assert!(Failure::Fault.matches(&run(&start, &minimal, 1000)));
+
+    for i in 0..minimal.len() {
+        let fewer = [&minimal[..i], &minimal[i + 1..]].concat();
+        assert!(!Failure::Fault.matches(&run(&start, &fewer, 1000)));
+    }
+
+    let looping = "3,100,1005,100,2,99";
+    let start = State::new_from_str(looping, std::iter::empty());
+    let inputs: Vec<Word> = [0, 0, 3, 0].iter().map(|&w| Word::from(w)).collect();
+    assert_eq!(minimize(&start, &inputs, &Failure::BudgetExhausted, 1000), [Word::from(3)]);
+}
@@ -0,0 +1,33 @@
+#[derive(Clone)]
+pub struct SplitMix(u64);
+
+impl SplitMix {
+    pub fn new(seed: u64) -> Self {
+        SplitMix(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn between(&mut self, lo: isize, hi: isize) -> isize {
+        lo + self.below((hi - lo + 1) as usize) as isize
+    }
+}
+
+#[test]
+fn deterministic() {
+    let a: Vec<_> = (0..4).scan(SplitMix::new(7), |r, _| Some(r.next_u64())).collect();
+    let b: Vec<_> = (0..4).scan(SplitMix::new(7), |r, _| Some(r.next_u64())).collect();
+    assert_eq!(a, b);
+    assert!((0..1000).scan(SplitMix::new(1), |r, _| Some(r.between(-9, 9))).all(|x| (-9..=9).contains(&x)));
+}
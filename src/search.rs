@@ -0,0 +1,194 @@
+use std::hash::Hash;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::intcode_full::{State, YieldReason, InputWord, Word};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    BreadthFirst,
+    DepthFirst,
+    BestFirst
+}
+
+pub trait Explore {
+    type Info: Clone;
+    type Key: Eq + Hash;
+
+    fn moves(&self, info: &Self::Info) -> Vec<Vec<InputWord>>;
+    fn advance(&self, info: &Self::Info, moves: &[InputWord], outputs: &[Word], reason: YieldReason) -> Option<Self::Info>;
+    fn key(&self, info: &Self::Info) -> Self::Key;
+    fn is_goal(&self, info: &Self::Info) -> bool;
+
+    fn cost(&self, _info: &Self::Info, depth: usize) -> usize {
+        depth
+    }
+}
+
+struct Node<I> {
+    state: Option<State>,
+    info: I,
+    parent: Option<usize>,
+    moves: Vec<InputWord>,
+    depth: usize
+}
+
+enum Frontier {
+    Queue(VecDeque<usize>),
+    Heap(BinaryHeap<Reverse<(usize, usize)>>)
+}
+
+pub struct Found<I> {
+    pub inputs: Vec<InputWord>,
+    pub moves: Vec<Vec<InputWord>>,
+    pub info: I,
+    pub state: State
+}
+
+pub struct Searcher<'e, E: Explore> {
+    explore: &'e E,
+    strategy: Strategy,
+    frontier: Frontier,
+    nodes: Vec<Node<E::Info>>,
+    seen: HashMap<E::Key, usize>
+}
+
+impl<'e, E: Explore> Searcher<'e, E> {
+    pub fn new(explore: &'e E, strategy: Strategy, start: State, info: E::Info) -> Self {
+        let frontier = match strategy {
+            Strategy::BestFirst => Frontier::Heap(BinaryHeap::new()),
+            _ => Frontier::Queue(VecDeque::new())
+        };
+
+        let mut searcher = Searcher { explore, strategy, frontier, nodes: Vec::new(), seen: HashMap::new() };
+        searcher.seen.insert(explore.key(&info), 0);
+        searcher.push(Node { state: Some(start), info, parent: None, moves: Vec::new(), depth: 0 });
+        searcher
+    }
+
+    fn push(&mut self, node: Node<E::Info>) {
+        let id = self.nodes.len();
+
+        match self.frontier {
+            Frontier::Queue(ref mut queue) => queue.push_back(id),
+            Frontier::Heap(ref mut heap) => heap.push(Reverse((self.explore.cost(&node.info, node.depth), id)))
+        }
+
+        self.nodes.push(node);
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        match self.frontier {
+            Frontier::Queue(ref mut queue) if self.strategy == Strategy::DepthFirst => queue.pop_back(),
+            Frontier::Queue(ref mut queue) => queue.pop_front(),
+            Frontier::Heap(ref mut heap) => heap.pop().map(|Reverse((_, id))| id)
+        }
+    }
+
+    fn path(&self, mut id: usize) -> Vec<Vec<InputWord>> {
+        let mut moves = Vec::new();
+        while let Some(parent) = self.nodes[id].parent {
+            moves.push(self.nodes[id].moves.clone());
+            id = parent;
+        }
+
+        moves.reverse();
+        moves
+    }
+
+    pub fn visited(&self) -> impl Iterator<Item=(&E::Key, &E::Info)> {
+        self.seen.iter().map(move |(key, &id)| (key, &self.nodes[id].info))
+    }
+
+    pub fn run(&mut self) -> Option<Found<E::Info>> {
+        while let Some(id) = self.pop() {
+            let state = self.nodes[id].state.take().expect("Frontier nodes keep their state.");
+            let info = self.nodes[id].info.clone();
+
+            if self.explore.is_goal(&info) {
+                let moves = self.path(id);
+                let inputs = moves.concat();
+                return Some(Found { inputs, moves, info, state });
+            }
+
+            for moves in self.explore.moves(&info) {
+                let mut child = state.clone();
+                for &input in moves.iter() {
+                    child.supply_input(input);
+                }
+
+                let (outputs, reason) = child.run_to_yield();
+                let child_info = match self.explore.advance(&info, &moves, &outputs, reason) {
+                    Some(child_info) => child_info,
+                    None => continue
+                };
+
+                let key = self.explore.key(&child_info);
+                if self.seen.contains_key(&key) {
+                    continue;
+                }
+
+                self.seen.insert(key, self.nodes.len());
+
+                let depth = self.nodes[id].depth + 1;
+                self.push(Node { state: Some(child), info: child_info, parent: Some(id), moves, depth });
+            }
+        }
+
+        None
+    }
+}
+
+pub fn search<E: Explore>(explore: &E, strategy: Strategy, start: State, info: E::Info) -> Option<Found<E::Info>> {
+    Searcher::new(explore, strategy, start, info).run()
+}
+
+#[cfg(test)]
+struct Accumulate(isize);
+
+#[cfg(test)]
+impl Explore for Accumulate {
+    type Info = isize;
+    type Key = isize;
+
+    fn moves(&self, _: &isize) -> Vec<Vec<InputWord>> {
+        vec![vec![1], vec![2]]
+    }
+
+    fn advance(&self, _: &isize, _: &[InputWord], outputs: &[Word], _: YieldReason) -> Option<isize> {
+        let sum = crate::intcode_full::word_narrow(outputs.last()?.clone())?;
+        Some(sum).filter(|&sum| sum <= self.0)
+    }
+
+    fn key(&self, &sum: &isize) -> isize {
+        sum
+    }
+
+    fn is_goal(&self, &sum: &isize) -> bool {
+        sum == self.0
+    }
+
+    fn cost(&self, &sum: &isize, _: usize) -> usize {
+        (self.0 - sum) as usize
+    }
+}
+
+#[test]
+fn accumulator_paths() {
+    let code = "3,100,1,100,101,101,4,101,1105,1,0";
+    let start = State::new_from_str(code, std::iter::empty());
+
+    let shortest = search(&Accumulate(7), Strategy::BreadthFirst, start.clone(), 0).unwrap();
+    assert_eq!(shortest.inputs.len(), 4);
+    assert_eq!(shortest.inputs.iter().sum::<isize>(), 7);
+
+    for &strategy in [Strategy::DepthFirst, Strategy::BestFirst].iter() {
+        let found = search(&Accumulate(7), strategy, start.clone(), 0).unwrap();
+        assert_eq!(found.inputs.iter().sum::<isize>(), 7);
+        assert_eq!(found.moves.len(), found.inputs.len());
+    }
+
+    let mut exhaustive = Searcher::new(&Accumulate(-1), Strategy::BreadthFirst, start, 0);
+    assert!(exhaustive.run().is_none());
+    assert_eq!(exhaustive.visited().count(), 1);
+}
@@ -0,0 +1,70 @@
+use crate::intcode_full::{State, YieldReason, Word};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Streamed {
+    Output(Word),
+    End(YieldReason)
+}
+
+pub struct OutputStream<'a> {
+    state: &'a mut State,
+    ended: bool
+}
+
+impl<'a> OutputStream<'a> {
+    pub fn new(state: &'a mut State) -> Self {
+        OutputStream { state, ended: false }
+    }
+
+    pub fn state(&self) -> &State {
+        self.state
+    }
+
+    pub fn words(self) -> impl Iterator<Item=Word> + 'a {
+        self.map_while(|item| match item {
+            Streamed::Output(w) => Some(w),
+            Streamed::End(_) => None
+        })
+    }
+}
+
+impl<'a> Iterator for OutputStream<'a> {
+    type Item = Streamed;
+
+    fn next(&mut self) -> Option<Streamed> {
+        if self.ended {
+            return None;
+        }
+
+        loop {
+            if let Some(w) = self.state.take_output() {
+                return Some(Streamed::Output(w));
+            }
+
+            if let Err(reason) = self.state.step() {
+                self.ended = true;
+                return Some(Streamed::End(reason));
+            }
+        }
+    }
+}
+
+impl State {
+    pub fn stream(&mut self) -> OutputStream<'_> {
+        OutputStream::new(self)
+    }
+}
+
+#[test]
+fn streams_lazily() {
+    let mut endless = State::new_from_str("104,1,1001,1,1,1,1105,1,0", std::iter::empty());
+    let first: Vec<Word> = endless.stream().words().take(3).collect();
+    assert_eq!(first, vec![1.into(), 2.into(), 3.into()]);
+
+    let mut echo = State::new_from_str("3,9,4,9,1105,1,0,99,0,0", [5isize, 6].iter().cloned());
+    let items: Vec<Streamed> = echo.stream().collect();
+    assert_eq!(items, vec![Streamed::Output(5.into()), Streamed::Output(6.into()), Streamed::End(YieldReason::WaitInput)]);
+
+    echo.supply_input(7);
+    assert_eq!(echo.stream().next(), Some(Streamed::Output(7.into())));
+}
@@ -0,0 +1,628 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+use boolinator::Boolinator;
+use num_traits::{Signed, Zero};
+use num_traits::cast::ToPrimitive;
+use num_integer::Integer;
+use num_bigint::ToBigUint;
+
+use crate::intcode_full::{Opcode, Pos, Word, InputWord, YieldReason, load_words};
+
+const MAX_PATHS: usize = 1024;
+const MAX_STEPS: usize = 100_000;
+
+pub type Var = usize;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Linear {
+    terms: BTreeMap<Var, Word>,
+    constant: Word
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Value {
+    Linear(Linear),
+    Opaque
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Relation {
+    Zero,
+    NonZero,
+    Negative,
+    NonNegative
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Constraint {
+    pub expr: Linear,
+    pub relation: Relation
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolicError {
+    IncorrectOpcode,
+    NonLinear,
+    SymbolicAddress,
+    OpaqueValue,
+    PathLimit,
+    StepLimit
+}
+
+impl Linear {
+    pub fn constant(w: Word) -> Self {
+        Linear { terms: BTreeMap::new(), constant: w }
+    }
+
+    pub fn var(v: Var) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(v, 1.into());
+        Linear { terms, constant: Word::zero() }
+    }
+
+    pub fn as_constant(&self) -> Option<&Word> {
+        self.terms.is_empty().as_some(&self.constant)
+    }
+
+    pub fn coefficient(&self, v: Var) -> Word {
+        self.terms.get(&v).cloned().unwrap_or_else(Word::zero)
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item=Var> + '_ {
+        self.terms.keys().cloned()
+    }
+
+    pub fn add(&self, other: &Linear) -> Linear {
+        let mut ret = self.clone();
+        for (&v, c) in other.terms.iter() {
+            *ret.terms.entry(v).or_insert_with(Word::zero) += c;
+        }
+
+        ret.terms.retain(|_, c| !c.is_zero());
+        ret.constant += &other.constant;
+        ret
+    }
+
+    pub fn scale(&self, k: &Word) -> Linear {
+        let mut ret = Linear::constant(&self.constant * k);
+        if !k.is_zero() {
+            ret.terms = self.terms.iter().map(|(&v, c)| (v, c * k)).collect();
+        }
+
+        ret
+    }
+
+    pub fn sub(&self, other: &Linear) -> Linear {
+        self.add(&other.scale(&(-1).into()))
+    }
+
+    pub fn eval(&self, assignment: &[Word]) -> Word {
+        self.terms.iter().fold(self.constant.clone(), |acc, (&v, c)| acc + c * &assignment[v])
+    }
+
+    pub fn equals(&self, w: impl Into<Word>) -> Constraint {
+        Constraint { expr: self.sub(&Linear::constant(w.into())), relation: Relation::Zero }
+    }
+}
+
+impl Value {
+    pub fn linear(&self) -> Option<&Linear> {
+        match self {
+            Value::Linear(l) => Some(l),
+            Value::Opaque => None
+        }
+    }
+
+    fn concrete(&self) -> Option<&Word> {
+        self.linear()?.as_constant()
+    }
+}
+
+impl Relation {
+    fn holds(self, w: &Word) -> bool {
+        match self {
+            Relation::Zero => w.is_zero(),
+            Relation::NonZero => !w.is_zero(),
+            Relation::Negative => w.is_negative(),
+            Relation::NonNegative => !w.is_negative()
+        }
+    }
+
+    fn negate(self) -> Self {
+        match self {
+            Relation::Zero => Relation::NonZero,
+            Relation::NonZero => Relation::Zero,
+            Relation::Negative => Relation::NonNegative,
+            Relation::NonNegative => Relation::Negative
+        }
+    }
+}
+
+impl Constraint {
+    pub fn holds(&self, assignment: &[Word]) -> bool {
+        self.relation.holds(&self.expr.eval(assignment))
+    }
+}
+
+#[derive(Clone)]
+pub struct Path {
+    ip: Pos,
+    rel_base: Word,
+    memory: HashMap<Pos, Value>,
+    inputs: Vec<Value>,
+    pub outputs: Vec<Value>,
+    pub constraints: Vec<Constraint>,
+    pub reason: YieldReason,
+    // Set once a read through a symbolic address produced an opaque value.
+    opaque: bool
+}
+
+enum Arg {
+    Immediate(Value),
+    Position(Value),
+    Relative(Value)
+}
+
+impl Path {
+    pub fn memory(&self, pos: InputWord) -> Value {
+        pos.to_biguint().map_or(Value::Opaque, |pos| self.load(&pos))
+    }
+
+    fn load(&self, pos: &Pos) -> Value {
+        self.memory.get(pos).cloned().unwrap_or_else(|| Value::Linear(Linear::constant(Word::zero())))
+    }
+
+    fn fetch(&self, pos: &Pos) -> Option<Word> {
+        self.load(pos).concrete().cloned()
+    }
+
+    fn address(&self, arg: &Arg) -> Result<Pos, SymbolicError> {
+        let (base, offset) = match arg {
+            Arg::Immediate(_) => return Err(SymbolicError::IncorrectOpcode),
+            Arg::Position(v) => (Word::zero(), v),
+            Arg::Relative(v) => (self.rel_base.clone(), v)
+        };
+
+        let offset = offset.concrete().ok_or(SymbolicError::SymbolicAddress)?;
+        (base + offset).to_biguint().ok_or(SymbolicError::IncorrectOpcode)
+    }
+
+    fn in_arg(&mut self, arg: &Arg) -> Result<Value, SymbolicError> {
+        match arg {
+            Arg::Immediate(v) => Ok(v.clone()),
+            _ => match self.address(arg) {
+                Ok(pos) => Ok(self.load(&pos)),
+                Err(SymbolicError::SymbolicAddress) => {
+                    self.opaque = true;
+                    Ok(Value::Opaque)
+                },
+                Err(e) => Err(e)
+            }
+        }
+    }
+
+    fn decode(&self) -> Result<(Opcode, Vec<Arg>), SymbolicError> {
+        let insn = self.fetch(&self.ip)
+            .and_then(|w| w.to_usize())
+            .ok_or(SymbolicError::IncorrectOpcode)?;
+
+        let (mut modes, opcode) = insn.div_rem(&100);
+        let (opcode, arg_count) = Opcode::parse(opcode).ok_or(SymbolicError::IncorrectOpcode)?;
+
+        let mut args = Vec::new();
+        for i in 1..=arg_count {
+            let raw = self.load(&(&self.ip + i));
+            let (modes_, mode) = modes.div_rem(&10);
+            modes = modes_;
+
+            args.push(match mode {
+                0 => Arg::Position(raw),
+                1 => Arg::Immediate(raw),
+                2 => Arg::Relative(raw),
+                _ => return Err(SymbolicError::IncorrectOpcode)
+            });
+        }
+
+        Ok((opcode, args))
+    }
+
+    fn branch(mut self, expr: Linear, relation: Relation) -> (Self, Self) {
+        let mut other = self.clone();
+        other.constraints.push(Constraint { expr: expr.clone(), relation: relation.negate() });
+        self.constraints.push(Constraint { expr, relation });
+        (self, other)
+    }
+
+    // Runs until the path yields, returning a sibling path when a symbolic condition forks it.
+    fn run(&mut self, domains: &[RangeInclusive<InputWord>]) -> Result<Option<Path>, SymbolicError> {
+        for _ in 0..MAX_STEPS {
+            let (opcode, args) = self.decode()?;
+            let next = &self.ip + 1usize + args.len();
+
+            let (cond, relation, jump, dest) = match opcode {
+                Opcode::Extension(_) => return Err(SymbolicError::IncorrectOpcode),
+
+                Opcode::Halt => {
+                    self.reason = YieldReason::Halt;
+                    return Ok(None);
+                },
+
+                Opcode::Add | Opcode::Mul => {
+                    let op1 = self.in_arg(&args[0])?;
+                    let op2 = self.in_arg(&args[1])?;
+                    let dest = self.address(&args[2])?;
+
+                    let result = match (opcode, op1.linear(), op2.linear()) {
+                        (Opcode::Add, Some(a), Some(b)) => Value::Linear(a.add(b)),
+                        (Opcode::Mul, Some(a), Some(b)) => match (a.as_constant(), b.as_constant()) {
+                            (Some(k), _) => Value::Linear(b.scale(k)),
+                            (_, Some(k)) => Value::Linear(a.scale(k)),
+                            _ => return Err(SymbolicError::NonLinear)
+                        },
+                        _ => Value::Opaque
+                    };
+
+                    self.memory.insert(dest, result);
+                    self.ip = next;
+                    continue;
+                },
+
+                Opcode::Input => {
+                    if self.inputs.is_empty() {
+                        self.reason = YieldReason::WaitInput;
+                        return Ok(None);
+                    }
+
+                    let dest = self.address(&args[0])?;
+                    let input = self.inputs.remove(0);
+                    self.memory.insert(dest, input);
+                    self.ip = next;
+                    continue;
+                },
+
+                Opcode::Output => {
+                    let op = self.in_arg(&args[0])?;
+                    self.outputs.push(op);
+                    self.ip = next;
+                    continue;
+                },
+
+                Opcode::AdjRelBase => {
+                    let op = self.in_arg(&args[0])?;
+                    self.rel_base += op.concrete().ok_or(SymbolicError::OpaqueValue)?;
+                    self.ip = next;
+                    continue;
+                },
+
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                    let cond = self.in_arg(&args[0])?;
+                    let target = self.in_arg(&args[1])?;
+                    let target = target.concrete()
+                        .ok_or(SymbolicError::SymbolicAddress)?
+                        .to_biguint()
+                        .ok_or(SymbolicError::IncorrectOpcode)?;
+
+                    let relation = match opcode {
+                        Opcode::JumpIfTrue => Relation::NonZero,
+                        _ => Relation::Zero
+                    };
+
+                    let cond = cond.linear().ok_or(SymbolicError::OpaqueValue)?.clone();
+                    (cond, relation, Some(target), None)
+                },
+
+                Opcode::LessThan | Opcode::Equals => {
+                    let op1 = self.in_arg(&args[0])?;
+                    let op2 = self.in_arg(&args[1])?;
+                    let dest = self.address(&args[2])?;
+
+                    let (a, b) = match (op1.linear(), op2.linear()) {
+                        (Some(a), Some(b)) => (a, b),
+                        _ => return Err(SymbolicError::OpaqueValue)
+                    };
+
+                    let relation = match opcode {
+                        Opcode::LessThan => Relation::Negative,
+                        _ => Relation::Zero
+                    };
+
+                    (a.sub(b), relation, None, Some(dest))
+                }
+            };
+
+            let set = |path: &mut Path, flag: bool| {
+                if let Some(dest) = dest.clone() {
+                    path.memory.insert(dest, Value::Linear(Linear::constant((flag as isize).into())));
+                    path.ip = next.clone();
+                } else if flag {
+                    path.ip = jump.clone().expect("Jump has a target.");
+                } else {
+                    path.ip = next.clone();
+                }
+            };
+
+            if let Some(c) = cond.as_constant() {
+                let flag = relation.holds(c);
+                set(self, flag);
+                continue;
+            }
+
+            let (mut yes, mut no) = self.clone().branch(cond, relation);
+            set(&mut yes, true);
+            set(&mut no, false);
+
+            match (feasible(&yes.constraints, domains), feasible(&no.constraints, domains)) {
+                (true, true) => {
+                    *self = yes;
+                    return Ok(Some(no));
+                },
+                (true, false) => *self = yes,
+                (false, true) => *self = no,
+                (false, false) => unreachable!()
+            }
+        }
+
+        Err(SymbolicError::StepLimit)
+    }
+}
+
+// Paths that hit an unsupported instruction are dropped from `paths`, their errors kept in `abandoned`.
+pub struct Exploration {
+    pub paths: Vec<Path>,
+    pub abandoned: Vec<SymbolicError>
+}
+
+pub struct Symbolic {
+    start: Path,
+    domains: Vec<RangeInclusive<InputWord>>
+}
+
+impl Symbolic {
+    pub fn new_from_str(s: &str) -> Self {
+        let memory = load_words(s.as_bytes()).expect("Examples are correct.")
+            .into_iter()
+            .enumerate()
+            .map(|(i, w)| (i.into(), Value::Linear(Linear::constant(w))))
+            .collect();
+
+        let start = Path {
+            ip: Pos::zero(),
+            rel_base: Word::zero(),
+            memory,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            reason: YieldReason::Halt,
+            opaque: false
+        };
+
+        Symbolic { start, domains: Vec::new() }
+    }
+
+    fn unknown(&mut self, domain: RangeInclusive<InputWord>) -> Value {
+        self.domains.push(domain);
+        Value::Linear(Linear::var(self.domains.len() - 1))
+    }
+
+    pub fn unknown_memory(&mut self, pos: InputWord, domain: RangeInclusive<InputWord>) -> Var {
+        let value = self.unknown(domain);
+        let pos = pos.to_biguint().expect("Correct address!");
+        self.start.memory.insert(pos, value);
+        self.domains.len() - 1
+    }
+
+    pub fn unknown_input(&mut self, domain: RangeInclusive<InputWord>) -> Var {
+        let value = self.unknown(domain);
+        self.start.inputs.push(value);
+        self.domains.len() - 1
+    }
+
+    pub fn concrete_input(&mut self, input: InputWord) {
+        self.start.inputs.push(Value::Linear(Linear::constant(input.into())));
+    }
+
+    pub fn explore(&self) -> Result<Exploration, SymbolicError> {
+        let mut pending = vec![self.start.clone()];
+        let mut done = Exploration { paths: Vec::new(), abandoned: Vec::new() };
+
+        while let Some(mut path) = pending.pop() {
+            if done.paths.len() + pending.len() >= MAX_PATHS {
+                return Err(SymbolicError::PathLimit);
+            }
+
+            match path.run(&self.domains) {
+                Ok(Some(fork)) => {
+                    pending.push(fork);
+                    pending.push(path);
+                },
+                Ok(None) => done.paths.push(path),
+                Err(e) => done.abandoned.push(e)
+            }
+        }
+
+        Ok(done)
+    }
+
+    // `Ok(None)` means no solution exists. When none is found but some paths were abandoned, or the
+    // goal gave up on a path holding opaque values, an error is returned instead, as those paths
+    // might have held one.
+    pub fn solve<F>(&self, goal: F) -> Result<Option<Vec<Word>>, SymbolicError>
+        where F: Fn(&Path) -> Option<Vec<Constraint>>
+    {
+        let mut exploration = self.explore()?;
+
+        for path in exploration.paths.iter() {
+            match goal(path) {
+                Some(mut constraints) => {
+                    constraints.extend(path.constraints.iter().cloned());
+                    if let Some(assignment) = solve_constraints(&constraints, &self.domains) {
+                        return Ok(Some(assignment));
+                    }
+                },
+                None if path.opaque => exploration.abandoned.push(SymbolicError::SymbolicAddress),
+                None => ()
+            }
+        }
+
+        match exploration.abandoned.first() {
+            Some(&e) => Err(e),
+            None => Ok(None)
+        }
+    }
+}
+
+fn feasible(constraints: &[Constraint], domains: &[RangeInclusive<InputWord>]) -> bool {
+    solve_constraints(constraints, domains).is_some()
+}
+
+fn ceil_div(a: &Word, b: &Word) -> Word {
+    -(-a).div_floor(b)
+}
+
+fn raise(lo: &mut Word, bound: Word) {
+    if bound > *lo {
+        *lo = bound;
+    }
+}
+
+fn lower(hi: &mut Word, bound: Word) {
+    if bound < *hi {
+        *hi = bound;
+    }
+}
+
+// Narrows `lo..=hi` to the values of `x` satisfying `c * x + r REL 0`, where `c` is nonzero.
+fn last_var_bounds(c: &Word, r: &Word, relation: Relation, lo: &mut Word, hi: &mut Word, excluded: &mut Vec<Word>) {
+    match relation {
+        Relation::Negative if c.is_positive() => lower(hi, (-r - Word::from(1)).div_floor(c)),
+        Relation::Negative => raise(lo, ceil_div(&(r + Word::from(1)), &-c)),
+        Relation::NonNegative if c.is_positive() => raise(lo, ceil_div(&-r, c)),
+        Relation::NonNegative => lower(hi, r.div_floor(&-c)),
+        Relation::Zero => {
+            let (q, m) = (-r).div_rem(c);
+            if m.is_zero() {
+                raise(lo, q.clone());
+                lower(hi, q);
+            } else {
+                lower(hi, lo.clone() - Word::from(1));
+            }
+        },
+        Relation::NonZero => {
+            let (q, m) = (-r).div_rem(c);
+            if m.is_zero() {
+                excluded.push(q);
+            }
+        }
+    }
+}
+
+fn solve_last(constraints: &[&Constraint], var: Var, domain: &RangeInclusive<InputWord>, assignment: &mut [Word]) -> bool {
+    let mut lo = Word::from(*domain.start());
+    let mut hi = Word::from(*domain.end());
+    let mut excluded = Vec::new();
+
+    assignment[var] = Word::zero();
+
+    for constraint in constraints {
+        let c = constraint.expr.coefficient(var);
+        let r = constraint.expr.eval(assignment);
+
+        if c.is_zero() {
+            if !constraint.relation.holds(&r) {
+                return false;
+            }
+        } else {
+            last_var_bounds(&c, &r, constraint.relation, &mut lo, &mut hi, &mut excluded);
+        }
+    }
+
+    while lo <= hi {
+        if !excluded.contains(&lo) {
+            assignment[var] = lo;
+            return true;
+        }
+
+        lo += 1;
+    }
+
+    false
+}
+
+fn search(constraints: &[Constraint], domains: &[RangeInclusive<InputWord>], var: Var, assignment: &mut Vec<Word>) -> bool {
+    let relevant: Vec<&Constraint> = constraints.iter()
+        .filter(|c| c.expr.vars().max().map_or(var == 0, |max| max == var))
+        .collect();
+
+    if var + 1 == domains.len() {
+        return solve_last(&relevant, var, &domains[var], assignment);
+    }
+
+    for value in domains[var].clone() {
+        assignment[var] = value.into();
+
+        if relevant.iter().all(|c| c.holds(assignment)) && search(constraints, domains, var + 1, assignment) {
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn solve_constraints(constraints: &[Constraint], domains: &[RangeInclusive<InputWord>]) -> Option<Vec<Word>> {
+    if domains.is_empty() {
+        let empty: Vec<Word> = Vec::new();
+        return constraints.iter().all(|c| c.holds(&empty)).as_some(empty);
+    }
+
+    let mut assignment = vec![Word::zero(); domains.len()];
+    search(constraints, domains, 0, &mut assignment).as_some(assignment)
+}
+
+#[test]
+fn solves_linear_memory() {
+    let code = "1,9,10,0,2,0,11,0,99,0,3,7";
+    let mut symbolic = Symbolic::new_from_str(code);
+    let x = symbolic.unknown_memory(9, 0..=99);
+
+    let solution = symbolic.solve(|path| Some(vec![path.memory(0).linear()?.equals(70)])).unwrap().unwrap();
+    assert_eq!(solution[x], Word::from(7));
+}
+
+#[test]
+fn branches_on_input() {
+    let code = "3,20,1007,20,10,21,1005,21,11,104,1,104,0,99";
+    let mut symbolic = Symbolic::new_from_str(code);
+    symbolic.unknown_input(-100..=100);
+
+    let paths = symbolic.explore().unwrap().paths;
+    assert_eq!(paths.len(), 2);
+
+    let big = symbolic.solve(|path| (path.outputs.len() == 2).as_some(Vec::new())).unwrap().unwrap();
+    assert!(big[0] >= Word::from(10));
+}
+
+#[test]
+fn skips_unsupported_paths() {
+    let mut symbolic = Symbolic::new_from_str("2,5,6,0,99,0,0");
+    symbolic.unknown_memory(5, 0..=9);
+    symbolic.unknown_memory(6, 0..=9);
+    assert_eq!(symbolic.solve(|_| Some(Vec::new())), Err(SymbolicError::NonLinear));
+
+    // Squares negative inputs, which is not linear, and prints the others.
+    let code = "3,20,1007,20,0,21,1005,21,12,4,20,99,2,20,20,22,99,0,0,0,0,0,0";
+    let mut symbolic = Symbolic::new_from_str(code);
+    symbolic.unknown_input(-10..=10);
+
+    let exploration = symbolic.explore().unwrap();
+    assert_eq!((exploration.paths.len(), exploration.abandoned), (1, vec![SymbolicError::NonLinear]));
+
+    let output = |w: isize| move |path: &Path| Some(vec![path.outputs.first()?.linear()?.equals(w)]);
+    assert_eq!(symbolic.solve(output(5)), Ok(Some(vec![5.into()])));
+    assert_eq!(symbolic.solve(output(-5)), Err(SymbolicError::NonLinear));
+
+    // Adds `mem[x]` and 5, so both x = 0 and x = 1 give 6, but the symbolic read is opaque.
+    let mut symbolic = Symbolic::new_from_str("1,0,8,0,99,0,0,0,5");
+    symbolic.unknown_memory(1, 0..=9);
+    assert_eq!(symbolic.solve(|path| Some(vec![path.memory(0).linear()?.equals(6)])), Err(SymbolicError::SymbolicAddress));
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use itertools::join;
+use num_traits::cast::ToPrimitive;
+
+use crate::intcode_full::{State, Opcode, YieldReason, Pos, Word, MAX_PARAMS};
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Source {
+    Input(usize),
+    Cell(Pos)
+}
+
+pub type Sources = BTreeSet<Source>;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Tainted {
+    pub value: Word,
+    pub sources: Sources
+}
+
+// Shadows every memory cell with the sources its value was computed from. Data flows through
+// arithmetic, comparisons and relative addressing; with `through_control_flow` every tainted
+// branch condition also taints everything written afterwards.
+#[derive(Clone)]
+pub struct Tracker {
+    state: State,
+    cells: HashMap<Pos, Sources>,
+    rel_base: Sources,
+    control: Option<Sources>,
+    pending: VecDeque<Sources>,
+    supplied: usize,
+    outputs: Vec<Tainted>
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Input(i) => write!(f, "input #{}", i),
+            Source::Cell(pos) => write!(f, "cell {}", pos)
+        }
+    }
+}
+
+impl fmt::Display for Tainted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.sources.is_empty() {
+            write!(f, "{} <- constant", self.value)
+        } else {
+            write!(f, "{} <- {}", self.value, join(&self.sources, ", "))
+        }
+    }
+}
+
+impl Tracker {
+    pub fn new(state: State) -> Self {
+        let mut tracker = Tracker {
+            state, cells: HashMap::new(), rel_base: Sources::new(), control: None, pending: VecDeque::new(), supplied: 0, outputs: Vec::new()
+        };
+
+        for _ in tracker.state.pending_inputs() {
+            tracker.push_input_source();
+        }
+
+        tracker
+    }
+
+    pub fn through_control_flow(&mut self) {
+        self.control.get_or_insert_with(Sources::new);
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn push_input_source(&mut self) {
+        self.pending.push_back(std::iter::once(Source::Input(self.supplied)).collect());
+        self.supplied += 1;
+    }
+
+    pub fn supply_input(&mut self, input: Word) {
+        self.state.supply_word(input);
+        self.push_input_source();
+    }
+
+    // Forwards sources across machines, e.g. from one amplifier's output into the next one's input.
+    pub fn supply_tainted(&mut self, input: Tainted) {
+        self.state.supply_word(input.value);
+        self.pending.push_back(input.sources);
+        self.supplied += 1;
+    }
+
+    pub fn taint_cell(&mut self, pos: Pos) {
+        self.cells.entry(pos.clone()).or_default().insert(Source::Cell(pos));
+    }
+
+    pub fn taint(&self, pos: &Pos) -> Sources {
+        self.cells.get(pos).cloned().unwrap_or_default()
+    }
+
+    pub fn read(&self, pos: &Pos) -> Tainted {
+        Tainted { value: self.state.read(pos), sources: self.taint(pos) }
+    }
+
+    pub fn control_taint(&self) -> Option<&Sources> {
+        self.control.as_ref()
+    }
+
+    // Sources of the value each parameter reads: the operand cell itself, plus for non-immediate
+    // modes the cell it points at and, for relative ones, whatever `rel_base` was computed from.
+    fn operand_sources(&self, ip: &Pos, code: usize, words: &[Word]) -> Vec<Sources> {
+        let mut modes = code / 100;
+
+        words.iter().enumerate().map(|(i, arg)| {
+            let mode = modes % 10;
+            modes /= 10;
+
+            let mut sources = self.taint(&(ip + 1usize + i));
+            let address = match mode {
+                0 => arg.to_biguint(),
+                2 => {
+                    sources.extend(self.rel_base.iter().cloned());
+                    (self.state.rel_base() + arg).to_biguint()
+                },
+                _ => None
+            };
+
+            sources.extend(address.map(|a| self.taint(&a)).unwrap_or_default());
+            sources
+        }).collect()
+    }
+
+    pub fn step(&mut self) -> Result<(), YieldReason> {
+        let ip = self.state.ip().clone();
+        let window: Vec<Word> = (0..=MAX_PARAMS).map(|i| self.state.read(&(&ip + i))).collect();
+        let decoded = window[0].to_usize().and_then(|code| {
+            let (opcode, params) = self.state.opcodes().lookup(code % 100)?;
+            Some((opcode, self.operand_sources(&ip, code, &window[1..=params])))
+        });
+
+        self.state.step()?;
+
+        let (opcode, sources) = decoded.expect("Executed instructions decode.");
+        let all: Sources = sources.iter().flatten().cloned().collect();
+
+        let written = match opcode {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => Some(&sources[0] | &sources[1]),
+            Opcode::Input => Some(self.pending.pop_front().unwrap_or_default()),
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                if let Some(control) = self.control.as_mut() {
+                    control.extend(all.iter().cloned());
+                }
+
+                None
+            },
+            Opcode::AdjRelBase => {
+                self.rel_base.extend(all.iter().cloned());
+                None
+            },
+            Opcode::Output | Opcode::Halt => None,
+            Opcode::Extension(_) => Some(all.clone())
+        };
+
+        let control = self.control.clone().unwrap_or_default();
+
+        if let (Some(written), Some(pos)) = (written, self.state.last_write().cloned()) {
+            self.cells.insert(pos, &written | &control);
+        }
+
+        while let Some(value) = self.state.take_output() {
+            self.outputs.push(Tainted { value, sources: &all | &control });
+        }
+
+        Ok(())
+    }
+
+    pub fn run_to_yield(&mut self) -> (Vec<Tainted>, YieldReason) {
+        let reason = loop {
+            if let Err(reason) = self.step() {
+                break reason;
+            }
+        };
+
+        (std::mem::take(&mut self.outputs), reason)
+    }
+}
+
+#[test]
+fn tracks_inputs_to_outputs() {
+    let inputs = |sources: &Sources| sources.iter().map(|s| match s {
+        Source::Input(i) => *i as isize,
+        Source::Cell(_) => -1
+    }).collect::<Vec<_>>();
+
+    let mut tracker = Tracker::new(State::new_from_str("3,13,3,14,1002,14,10,15,4,15,4,13,99,0,0,0", [4, 5].iter().cloned()));
+    let (outputs, reason) = tracker.run_to_yield();
+    assert_eq!(reason, YieldReason::Halt);
+    assert_eq!(outputs.iter().map(|t| (t.value.clone(), inputs(&t.sources))).collect::<Vec<_>>(),
+               [(50.into(), vec![1]), (4.into(), vec![0])]);
+
+    let branchy = "3,20,1005,20,8,104,0,99,104,1,99";
+    let mut data = Tracker::new(State::new_from_str(branchy, [7].iter().cloned()));
+    let mut control = data.clone();
+    control.through_control_flow();
+
+    assert_eq!(data.run_to_yield().0[0].to_string(), "1 <- constant");
+    assert_eq!(control.run_to_yield().0[0].to_string(), "1 <- input #0");
+
+    let mut patched = Tracker::new(State::new_from_str("1,0,0,0,99", std::iter::empty()));
+    patched.taint_cell(1usize.into());
+    patched.run_to_yield();
+    assert_eq!(patched.read(&0usize.into()).to_string(), "2 <- cell 1");
+}
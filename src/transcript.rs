@@ -0,0 +1,361 @@
+use std::fmt;
+use std::iter::empty;
+
+use itertools::join;
+
+use crate::intcode_full::{State, YieldReason, Limit, Until, Event, InputWord, Pos, Word, load_words};
+
+const HEADER: &str = "intcode-transcript 1";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Entry {
+    Patch(Pos, Word),
+    Input(Word),
+    Yield(Vec<Word>, YieldReason),
+    Until(Vec<Until>, Vec<Word>, Event)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Transcript {
+    pub program_hash: u64,
+    pub entries: Vec<Entry>
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ParseError {
+    Header,
+    Line(usize)
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ReplayError {
+    ProgramHash { expected: u64, actual: u64 },
+    Yield { entry: usize, expected: (Vec<Word>, YieldReason), actual: (Vec<Word>, YieldReason) },
+    Until { entry: usize, expected: (Vec<Word>, Event), actual: (Vec<Word>, Event) }
+}
+
+pub fn program_hash(s: &str) -> u64 {
+    let canonical = load_words(s.as_bytes()).map_or_else(|_| s.trim().to_string(), |words| join(words, ","));
+
+    canonical.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn parse_limit(s: &str) -> Option<Limit> {
+    match s {
+        "Cells" => Some(Limit::Cells),
+        "Address" => Some(Limit::Address),
+        "WordBits" => Some(Limit::WordBits),
+        "Inputs" => Some(Limit::Inputs),
+        "Outputs" => Some(Limit::Outputs),
+        _ => None
+    }
+}
+
+fn parse_reason(s: &str) -> Option<YieldReason> {
+    match s {
+        "IncorrectOpcode" => Some(YieldReason::IncorrectOpcode),
+        "NegativeAddress" => Some(YieldReason::NegativeAddress),
+        "NoSuchArg" => Some(YieldReason::NoSuchArg),
+        "WaitInput" => Some(YieldReason::WaitInput),
+        "Halt" => Some(YieldReason::Halt),
+        "Overflow" => Some(YieldReason::Overflow),
+        "WriteProtected" => Some(YieldReason::WriteProtected),
+        _ => {
+            if let Some(limit) = s.strip_prefix("LimitExceeded(").and_then(|s| s.strip_suffix(')')) {
+                return parse_limit(limit).map(YieldReason::LimitExceeded);
+            }
+
+            let code = s.strip_prefix("Trap(")?.strip_suffix(')')?;
+            Some(YieldReason::Trap(code.parse().ok()?))
+        }
+    }
+}
+
+fn parse_until(s: &str) -> Option<Until> {
+    let mut parts = s.splitn(2, ':');
+
+    match (parts.next()?, parts.next()) {
+        ("outputs", Some(n)) => Some(Until::Outputs(n.parse().ok()?)),
+        ("input", None) => Some(Until::InputRequested),
+        ("reached", Some(pos)) => Some(Until::Reached(pos.parse().ok()?)),
+        ("write", Some(pos)) => Some(Until::WriteTo(pos.parse().ok()?)),
+        _ => None
+    }
+}
+
+fn format_until(until: &Until) -> String {
+    match until {
+        Until::Outputs(n) => format!("outputs:{}", n),
+        Until::InputRequested => "input".to_string(),
+        Until::Reached(pos) => format!("reached:{}", pos),
+        Until::WriteTo(pos) => format!("write:{}", pos)
+    }
+}
+
+fn parse_event(s: &str) -> Option<Event> {
+    match s {
+        "Outputs" => Some(Event::Outputs),
+        "InputRequested" => Some(Event::InputRequested),
+        _ => {
+            if let Some(pos) = s.strip_prefix("Reached(").and_then(|s| s.strip_suffix(')')) {
+                return Some(Event::Reached(pos.parse().ok()?));
+            }
+
+            if let Some(pos) = s.strip_prefix("Write(").and_then(|s| s.strip_suffix(')')) {
+                return Some(Event::Write(pos.parse().ok()?));
+            }
+
+            parse_reason(s).map(Event::Yield)
+        }
+    }
+}
+
+fn format_event(event: &Event) -> String {
+    match event {
+        Event::Reached(pos) => format!("Reached({})", pos),
+        Event::Write(pos) => format!("Write({})", pos),
+        Event::Yield(reason) => format!("{:?}", reason),
+        _ => format!("{:?}", event)
+    }
+}
+
+fn parse_outputs(s: Option<&str>) -> Option<Vec<Word>> {
+    match s {
+        None | Some("") => Some(Vec::new()),
+        Some(outputs) => outputs.split(',').map(|w| w.parse().ok()).collect()
+    }
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut parts = line.splitn(4, ' ');
+
+    match parts.next()? {
+        "patch" => Some(Entry::Patch(parts.next()?.parse().ok()?, parts.next()?.parse().ok()?)),
+        "input" => Some(Entry::Input(parts.next()?.parse().ok()?)),
+        "yield" => {
+            let reason = parse_reason(parts.next()?)?;
+            let outputs = parse_outputs(parts.next())?;
+
+            Some(Entry::Yield(outputs, reason))
+        },
+        "until" => {
+            let until = parts.next()?.split(',').map(parse_until).collect::<Option<_>>()?;
+            let event = parse_event(parts.next()?)?;
+            let outputs = parse_outputs(parts.next())?;
+
+            Some(Entry::Until(until, outputs, event))
+        },
+        _ => None
+    }
+}
+
+impl Transcript {
+    pub fn new(program: &str) -> Self {
+        Transcript { program_hash: program_hash(program), entries: Vec::new() }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+
+        if lines.next().map(|(_, l)| l.trim()) != Some(HEADER) {
+            return Err(ParseError::Header);
+        }
+
+        let program_hash = lines.next()
+            .and_then(|(_, l)| l.trim().strip_prefix("program "))
+            .and_then(|h| u64::from_str_radix(h, 16).ok())
+            .ok_or(ParseError::Header)?;
+
+        let entries = lines
+            .map(|(i, l)| parse_entry(l.trim()).ok_or(ParseError::Line(i + 1)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Transcript { program_hash, entries })
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item=&Word> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Input(w) => Some(w),
+            _ => None
+        })
+    }
+
+    pub fn replay(&self, program: &str) -> Result<(), ReplayError> {
+        let actual = program_hash(program);
+        if actual != self.program_hash {
+            return Err(ReplayError::ProgramHash { expected: self.program_hash, actual });
+        }
+
+        let mut state = State::new_from_str(program, empty());
+
+        for (entry, e) in self.entries.iter().enumerate() {
+            match e {
+                Entry::Patch(pos, value) => state.write(pos.clone(), value.clone()),
+                Entry::Input(w) => state.supply_word(w.clone()),
+                Entry::Yield(outputs, reason) => {
+                    let actual = state.run_to_yield();
+                    if (&actual.0, actual.1) != (outputs, *reason) {
+                        let expected = (outputs.clone(), *reason);
+                        return Err(ReplayError::Yield { entry, expected, actual });
+                    }
+                },
+                Entry::Until(until, outputs, event) => {
+                    let actual = state.run_until(until);
+                    if (&actual.0, &actual.1) != (outputs, event) {
+                        let expected = (outputs.clone(), event.clone());
+                        return Err(ReplayError::Until { entry, expected, actual });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "program {:016x}", self.program_hash)?;
+
+        for e in self.entries.iter() {
+            match e {
+                Entry::Patch(pos, value) => writeln!(f, "patch {} {}", pos, value)?,
+                Entry::Input(w) => writeln!(f, "input {}", w)?,
+                Entry::Yield(outputs, reason) => writeln!(f, "yield {:?} {}", reason, join(outputs, ","))?,
+                Entry::Until(until, outputs, event) => {
+                    let until = join(until.iter().map(format_until), ",");
+                    writeln!(f, "until {} {} {}", until, format_event(event), join(outputs, ","))?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Solvers drive either a plain `State` or a `Recorder`, so recording stays out of the solve path.
+pub trait Machine {
+    fn state(&self) -> &State;
+    fn supply_word(&mut self, input: Word);
+    fn run_until(&mut self, until: &[Until]) -> (Vec<Word>, Event);
+}
+
+impl Machine for State {
+    fn state(&self) -> &State {
+        self
+    }
+
+    fn supply_word(&mut self, input: Word) {
+        State::supply_word(self, input)
+    }
+
+    fn run_until(&mut self, until: &[Until]) -> (Vec<Word>, Event) {
+        State::run_until(self, until)
+    }
+}
+
+impl Machine for Recorder {
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn supply_word(&mut self, input: Word) {
+        Recorder::supply_word(self, input)
+    }
+
+    fn run_until(&mut self, until: &[Until]) -> (Vec<Word>, Event) {
+        Recorder::run_until(self, until)
+    }
+}
+
+pub struct Recorder {
+    state: State,
+    transcript: Transcript
+}
+
+impl Recorder {
+    pub fn new_from_str(s: &str, inputs: impl Iterator<Item=InputWord>) -> Self {
+        let mut recorder = Recorder { state: State::new_from_str(s, empty()), transcript: Transcript::new(s) };
+        for input in inputs {
+            recorder.supply_input(input);
+        }
+
+        recorder
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    pub fn into_transcript(self) -> Transcript {
+        self.transcript
+    }
+
+    pub fn keep_history(&mut self, len: usize) {
+        self.state.keep_history(len);
+    }
+
+    pub fn track_calls(&mut self) {
+        self.state.track_calls();
+    }
+
+    pub fn write_memory(&mut self, pos: InputWord, value: InputWord) {
+        self.try_write_memory(&pos.into(), value.into()).expect("Correct address!")
+    }
+
+    pub fn try_write_memory(&mut self, pos: &Word, value: Word) -> Result<(), YieldReason> {
+        let pos = pos.to_biguint().ok_or(YieldReason::NegativeAddress)?;
+        self.state.write(pos.clone(), value.clone());
+        self.transcript.entries.push(Entry::Patch(pos, value));
+        Ok(())
+    }
+
+    pub fn supply_input(&mut self, input: InputWord) {
+        self.supply_word(input.into())
+    }
+
+    pub fn supply_word(&mut self, input: Word) {
+        self.state.supply_word(input.clone());
+        self.transcript.entries.push(Entry::Input(input));
+    }
+
+    pub fn run_to_yield(&mut self) -> (Vec<Word>, YieldReason) {
+        let (outputs, reason) = self.state.run_to_yield();
+        self.transcript.entries.push(Entry::Yield(outputs.clone(), reason));
+        (outputs, reason)
+    }
+
+    pub fn run_until(&mut self, until: &[Until]) -> (Vec<Word>, Event) {
+        let (outputs, event) = self.state.run_until(until);
+        self.transcript.entries.push(Entry::Until(until.to_vec(), outputs.clone(), event.clone()));
+        (outputs, event)
+    }
+}
+
+#[test]
+fn round_trip_and_replay() {
+    let echo = "3,100,4,100,1008,100,10,101,1006,101,0,99";
+
+    let mut recorder = Recorder::new_from_str(echo, [7isize].iter().cloned());
+    recorder.run_to_yield();
+    recorder.supply_input(10);
+    recorder.run_until(&[Until::Outputs(1), Until::WriteTo(101usize.into())]);
+    recorder.run_to_yield();
+
+    let transcript = recorder.into_transcript();
+    let text = transcript.to_string();
+    assert_eq!(Transcript::parse(&text), Ok(transcript.clone()));
+    assert_eq!(transcript.replay(echo), Ok(()));
+
+    assert!(text.contains("until outputs:1,write:101 Outputs 10\n"));
+
+    let broken = text.replace("Outputs 10", "Outputs 11");
+    assert!(matches!(Transcript::parse(&broken).unwrap().replay(echo), Err(ReplayError::Until { entry: 3, .. })));
+}